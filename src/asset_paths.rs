@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+
+use crate::config::WORLD_GEN_CONFIG_PATH;
+use crate::input::INPUT_BINDINGS_PATH;
+
+/// Where the game finds its assets, relative to the asset folder. Apps embedding the world can point these somewhere else.
+#[derive(Resource, Clone, Debug)]
+pub struct AssetPaths {
+    pub world_config: String,
+    pub tree: String,
+    pub candy_cane: String,
+    pub sled: String, // scene inside the glb
+    pub snowflake: String,
+    pub input_bindings: String,
+}
+
+impl Default for AssetPaths {
+    fn default() -> Self {
+        Self {
+            world_config: WORLD_GEN_CONFIG_PATH.to_string(),
+            tree: "snowy_pine_tree.glb".to_string(),
+            candy_cane: "candy_cane.glb".to_string(),
+            sled: "sled.glb#Scene0".to_string(),
+            snowflake: "snowflake.png".to_string(),
+            input_bindings: INPUT_BINDINGS_PATH.to_string(),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+// headless world generation: renders the terrain of a rectangle into png maps, no window or gpu needed.
+// cargo run --bin worldgen -- --seed 67 --min -512,-512 --max 512,512 --resolution 2 --out worldgen_out
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use image::{ImageBuffer, Luma, Rgb};
+
+use bevy_winter::biome::{Biome, BiomeMap, PropKind};
+use bevy_winter::chunks::{get_height, prop_density, should_candy_spawn, should_shrub_spawn, should_tree_spawn};
+use bevy_winter::cli::{seed_from_str, CliError, DEFAULT_SEED};
+use bevy_winter::config::WorldGenConfig;
+use bevy_winter::noise::NoiseGenerators;
+
+// fixed height range of the heightmap, so maps of different runs can be compared pixel by pixel
+const HEIGHT_MIN: f32 = -64.0;
+const HEIGHT_MAX: f32 = 128.0;
+const MAX_PIXELS: u64 = 64_000_000;
+
+const USAGE: &str = "usage: worldgen [--seed SEED] [--min X,Z] [--max X,Z] [--resolution UNITS_PER_PIXEL] [--config FILE] [--out DIR]";
+
+struct Args {
+    seed: u32,
+    min: (f64, f64),
+    max: (f64, f64),
+    resolution: f64,
+    config: Option<PathBuf>,
+    out: PathBuf,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            min: (-512.0, -512.0),
+            max: (512.0, 512.0),
+            resolution: 2.0,
+            config: None,
+            out: PathBuf::from("worldgen_out"),
+        }
+    }
+}
+
+fn parse_pair(value: &str) -> Result<(f64, f64), String> {
+    let (x, z) = value.split_once(',').ok_or_else(|| format!("expected X,Z but got {value}"))?;
+    let parse = |v: &str| v.trim().parse::<f64>().map_err(|e| format!("{v}: {e}"));
+    Ok((parse(x)?, parse(z)?))
+}
+
+fn parse_args() -> Result<Args, CliError> {
+    let mut args = Args::default();
+    let mut iter = std::env::args().skip(1);
+
+    while let Some(flag) = iter.next() {
+        if flag == "--help" || flag == "-h" {
+            return Err(CliError::Help);
+        }
+        let value = iter.next().ok_or_else(|| format!("{flag} needs a value"))?;
+        match flag.as_str() {
+            // text seeds get hashed the same way the game does it
+            "--seed" => args.seed = seed_from_str(&value),
+            "--min" => args.min = parse_pair(&value)?,
+            "--max" => args.max = parse_pair(&value)?,
+            "--resolution" => args.resolution = value.parse().map_err(|e| format!("--resolution: {e}"))?,
+            "--config" => args.config = Some(PathBuf::from(value)),
+            "--out" => args.out = PathBuf::from(value),
+            _ => return Err(CliError::Invalid(format!("unknown option {flag}\n{USAGE}"))),
+        }
+    }
+
+    if args.max.0 <= args.min.0 || args.max.1 <= args.min.1 {
+        return Err(CliError::Invalid("--max has to be bigger than --min on both axes".to_string()));
+    }
+    if args.resolution <= 0.0 {
+        return Err(CliError::Invalid("--resolution has to be positive".to_string()));
+    }
+    Ok(args)
+}
+
+// the same file the game loads, or the defaults if there is none
+fn load_config(path: Option<&PathBuf>) -> Result<WorldGenConfig, String> {
+    let default_path = PathBuf::from("assets").join(bevy_winter::config::WORLD_GEN_CONFIG_PATH);
+    let path = match path {
+        Some(path) => path.clone(),
+        None if default_path.exists() => default_path,
+        None => return Ok(WorldGenConfig::default()),
+    };
+    let bytes = std::fs::read(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+    WorldGenConfig::from_ron(&bytes).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn biome_color(biome: Biome) -> [f32; 3] {
+    match biome {
+        Biome::Plains => [0.55, 0.80, 0.45],
+        Biome::Forest => [0.13, 0.45, 0.20],
+        Biome::FrozenLake => [0.45, 0.70, 0.95],
+        Biome::GlacialMountains => [0.85, 0.88, 0.95],
+        Biome::Tundra => [0.65, 0.60, 0.45],
+    }
+}
+
+fn run() -> Result<(), CliError> {
+    let args = parse_args()?;
+    let config = load_config(args.config.as_ref())?;
+    let noise = NoiseGenerators::new(args.seed);
+    let biomes = BiomeMap::new(&noise, &config);
+
+    let width = ((args.max.0 - args.min.0) / args.resolution).ceil() as u32;
+    let height = ((args.max.1 - args.min.1) / args.resolution).ceil() as u32;
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err(CliError::Invalid(format!("{width}x{height} pixels is too big, use a bigger --resolution")));
+    }
+
+    let mut heightmap = ImageBuffer::<Luma<u16>, _>::new(width, height);
+    let mut biome_map = ImageBuffer::<Rgb<u8>, _>::new(width, height);
+    // one channel per prop (tree, candy cane, shrub): dim = density, full = the prop spawns here
+    let mut prop_map = ImageBuffer::<Rgb<u8>, _>::new(width, height);
+    let (mut lowest, mut highest) = (f32::MAX, f32::MIN);
+
+    for pz in 0..height {
+        for px in 0..width {
+            // middle of the pixel
+            let x = args.min.0 + (px as f64 + 0.5) * args.resolution;
+            let z = args.min.1 + (pz as f64 + 0.5) * args.resolution;
+
+            let h = get_height(x, z, &noise, &config);
+            lowest = lowest.min(h);
+            highest = highest.max(h);
+            let t = ((h - HEIGHT_MIN) / (HEIGHT_MAX - HEIGHT_MIN)).clamp(0.0, 1.0);
+            heightmap.put_pixel(px, pz, Luma([(t * u16::MAX as f32).round() as u16]));
+
+            let weights = biomes.weights(x, z);
+            let color = [0, 1, 2].map(|i| (weights.blend(|b| biome_color(b)[i]) * 255.0).round() as u8);
+            biome_map.put_pixel(px, pz, Rgb(color));
+
+            let props = [
+                (PropKind::Tree, should_tree_spawn(x, z, &noise, &config)),
+                (PropKind::CandyCane, should_candy_spawn(x, z, &noise, &config)),
+                (PropKind::Shrub, should_shrub_spawn(x, z, &noise, &config)),
+            ];
+            let channels = props.map(|(kind, spawns)| {
+                if spawns {
+                    255
+                } else {
+                    (prop_density(kind, x, z, &noise, &config).clamp(0.0, 1.0) * 127.0).round() as u8
+                }
+            });
+            prop_map.put_pixel(px, pz, Rgb(channels));
+        }
+    }
+
+    std::fs::create_dir_all(&args.out).map_err(|e| format!("{}: {e}", args.out.display()))?;
+    let save = |name: &str, result: Result<(), image::ImageError>| {
+        result.map_err(|e| format!("{name}: {e}"))
+    };
+    save("heightmap.png", heightmap.save(args.out.join("heightmap.png")))?;
+    save("biomes.png", biome_map.save(args.out.join("biomes.png")))?;
+    save("props.png", prop_map.save(args.out.join("props.png")))?;
+
+    println!(
+        "seed {} | {}x{} px | heights {:.2} to {:.2} (map range {} to {}) | written to {}",
+        args.seed, width, height, lowest, highest, HEIGHT_MIN, HEIGHT_MAX, args.out.display()
+    );
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Help) => {
+            eprintln!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use noise::NoiseFn;
+
+use crate::config::WorldGenConfig;
+use crate::noise::NoiseGenerators;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Biome {
+    Plains,
+    Forest,
+    FrozenLake,
+    GlacialMountains,
+    Tundra,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PropKind {
+    Tree,
+    CandyCane,
+    Shrub,
+}
+
+const ROCK_COLOR: [f32; 3] = [0.36, 0.36, 0.40];
+
+// 0 below lo, 1 above hi, linear in between
+fn ramp(v: f32, lo: f32, hi: f32) -> f32 {
+    ((v - lo) / (hi - lo)).clamp(0.0, 1.0)
+}
+
+impl Biome {
+    pub const ALL: [Biome; 5] = [
+        Biome::Plains,
+        Biome::Forest,
+        Biome::FrozenLake,
+        Biome::GlacialMountains,
+        Biome::Tundra,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    // terrain height of this biome alone, get_height blends these with the biome weights
+    pub fn height(self, world_x: f64, world_z: f64, noise: &NoiseGenerators, config: &WorldGenConfig) -> f32 {
+        // base height from noise
+        let base_h = noise.height.get([world_x * config.noise_freq, world_z * config.noise_freq]) as f32;
+
+        let erosion = noise.height.get([world_x * 0.03, world_z * 0.03]) as f32 * 2.0;
+
+        // rounded snow drifts
+        let drift_n = noise.height.get([world_x * 0.01, world_z * 0.01]) as f32;
+        let drifts = drift_n.abs().powf(2.0) * 6.0;
+
+        match self {
+            Biome::Plains => base_h * config.noise_amp * config.plains_scale + erosion + drifts,
+            Biome::Forest => base_h * config.noise_amp * config.forest_scale + erosion + drifts,
+            // flat ice
+            Biome::FrozenLake => config.lake_level,
+            Biome::GlacialMountains => {
+                let ridge_freq = config.noise_freq * 1.5;
+                let ridge = noise.ridge.get([world_x * ridge_freq, world_z * ridge_freq]) as f32;
+                let peaks = (ridge * 0.5 + 0.5).clamp(0.0, 1.0);
+
+                base_h * config.noise_amp + peaks * peaks * config.mountain_height + erosion
+            }
+            // low and rolling, no big hills
+            Biome::Tundra => base_h * config.noise_amp * config.tundra_scale + drifts,
+        }
+    }
+
+    // vertex colour, normal_y tells how steep the ground is (1 = flat)
+    pub fn ground_color(self, normal_y: f32, config: &WorldGenConfig) -> [f32; 3] {
+        match self {
+            Biome::Plains => [0.88, 0.93, 1.0],
+            Biome::Forest => [0.84, 0.90, 0.98],
+            Biome::FrozenLake => [0.72, 0.86, 0.97],
+            Biome::GlacialMountains => {
+                // snow doesn't stay on steep slopes, show the rock
+                let snow = [0.94, 0.97, 1.0];
+                let rock = ramp(config.rock_slope - normal_y, 0.0, 0.1);
+                [0, 1, 2].map(|i| snow[i] * (1.0 - rock) + ROCK_COLOR[i] * rock)
+            }
+            Biome::Tundra => [0.86, 0.89, 0.88],
+        }
+    }
+
+    // the prop table, how dense each prop spawns in this biome
+    pub fn prop_density(self, kind: PropKind, config: &WorldGenConfig) -> f32 {
+        match (self, kind) {
+            (Biome::Plains, PropKind::Tree) => config.plains_tree_density,
+            (Biome::Forest, PropKind::Tree) => config.forest_tree_density,
+            (Biome::GlacialMountains, PropKind::Tree) => config.mountain_tree_density,
+            (Biome::Tundra, PropKind::Shrub) => config.shrub_density,
+            (Biome::Plains | Biome::Forest | Biome::Tundra, PropKind::CandyCane) => config.candy_density,
+            _ => 0.0,
+        }
+    }
+
+    // how much the snow slows a sled down, fresh powder is slow and lake ice barely brakes at all
+    pub fn sled_friction(self) -> f32 {
+        match self {
+            Biome::Plains => 0.08,
+            Biome::Forest => 0.12, // deep powder between the trees
+            Biome::FrozenLake => 0.02,
+            Biome::GlacialMountains => 0.05, // hard packed, fast
+            Biome::Tundra => 0.10, // wind crust
+        }
+    }
+
+    // how much of the wind gets through, trees shelter and nothing stops it on open ground
+    pub fn wind_exposure(self) -> f32 {
+        match self {
+            Biome::Plains => 1.0,
+            Biome::Forest => 0.4,
+            Biome::FrozenLake => 1.2,
+            Biome::GlacialMountains => 1.4,
+            Biome::Tundra => 1.3,
+        }
+    }
+}
+
+/// How much every biome contributes at one world position. The weights always add up to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BiomeWeights {
+    weights: [f32; Biome::ALL.len()],
+}
+
+impl BiomeWeights {
+    pub fn get(&self, biome: Biome) -> f32 {
+        self.weights[biome.index()]
+    }
+
+    // biome with the biggest weight, this is what the HUD shows
+    pub fn dominant(&self) -> Biome {
+        Biome::ALL
+            .into_iter()
+            .fold(Biome::Plains, |best, b| if self.get(b) > self.get(best) { b } else { best })
+    }
+
+    // weighted average of a per-biome value, biomes with weight 0 aren't evaluated at all
+    pub fn blend(&self, value: impl Fn(Biome) -> f32) -> f32 {
+        Biome::ALL
+            .into_iter()
+            .filter(|&b| self.get(b) > 0.0)
+            .map(|b| self.get(b) * value(b))
+            .sum()
+    }
+}
+
+/// The one place that decides which biome is where. Terrain height, snow colour, props and the HUD all go through this.
+pub struct BiomeMap<'a> {
+    noise: &'a NoiseGenerators,
+    config: &'a WorldGenConfig,
+}
+
+impl<'a> BiomeMap<'a> {
+    pub fn new(noise: &'a NoiseGenerators, config: &'a WorldGenConfig) -> Self {
+        Self { noise, config }
+    }
+
+    pub fn weights(&self, world_x: f64, world_z: f64) -> BiomeWeights {
+        let freq = self.config.biome_freq;
+        let biome_val = self.noise.biome.get([world_x * freq, world_z * freq]) as f32;
+        let climate_val = self.noise.climate.get([world_x * freq, world_z * freq]) as f32;
+
+        // temperate half: plains -> forest along the biome noise (t 0 = plains, 1 = forest)
+        let t = ramp(biome_val, -0.2, 0.3);
+        // cold half: frozen lakes in the lows, tundra in between, glaciers in the highs
+        let cold = ramp(climate_val, 0.05, 0.4);
+        let lake = 1.0 - ramp(biome_val, -0.35, -0.15);
+        let mountains = ramp(biome_val, 0.15, 0.35);
+        let tundra = 1.0 - lake - mountains;
+
+        let temperate = 1.0 - cold;
+        BiomeWeights {
+            weights: [
+                temperate * (1.0 - t),
+                temperate * t,
+                cold * lake,
+                cold * mountains,
+                cold * tundra,
+            ],
+        }
+    }
+
+    pub fn biome_at(&self, world_x: f64, world_z: f64) -> Biome {
+        self.weights(world_x, world_z).dominant()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::mesh::{Mesh, VertexAttributeValues};
+    use crate::chunks::{calc_to_generate_chunk, get_height, prop_density, should_candy_spawn, should_shrub_spawn, should_tree_spawn};
+    use crate::deform::TerrainEdits;
+    use crate::world_gen::ChunkCoord;
+    #[cfg(feature = "hud")]
+    use crate::hud::detect_biome;
+
+    fn sample_positions() -> impl Iterator<Item = (f64, f64)> {
+        (-40..40).flat_map(|x| (-40..40).map(move |z| (x as f64 * 23.0, z as f64 * 23.0)))
+    }
+
+    #[test]
+    fn weights_add_up_to_one() {
+        let noise = NoiseGenerators::new(67);
+        let config = WorldGenConfig::default();
+        let map = BiomeMap::new(&noise, &config);
+
+        for (x, z) in sample_positions() {
+            let w = map.weights(x, z);
+            let sum: f32 = Biome::ALL.iter().map(|&b| w.get(b)).sum();
+            assert!((sum - 1.0).abs() < 1e-5, "weights at {x} {z} sum to {sum}");
+            assert!(Biome::ALL.iter().all(|&b| w.get(b) >= 0.0));
+        }
+    }
+
+    #[test]
+    fn height_props_and_hud_use_the_weights() {
+        let noise = NoiseGenerators::new(67);
+        let config = WorldGenConfig::default();
+        let map = BiomeMap::new(&noise, &config);
+        let spawns = [
+            (PropKind::Tree, should_tree_spawn as fn(f64, f64, &NoiseGenerators, &WorldGenConfig) -> bool),
+            (PropKind::CandyCane, should_candy_spawn),
+            (PropKind::Shrub, should_shrub_spawn),
+        ];
+
+        let mut spawned = 0;
+        for (x, z) in sample_positions() {
+            let weights = map.weights(x, z);
+            assert_eq!(get_height(x, z, &noise, &config), weights.blend(|b| b.height(x, z, &noise, &config)));
+
+            for (kind, should_spawn) in spawns {
+                let density = weights.blend(|b| b.prop_density(kind, &config));
+                assert_eq!(prop_density(kind, x, z, &noise, &config), density);
+                // nothing grows where none of the biomes here has it
+                if should_spawn(x, z, &noise, &config) {
+                    assert!(density > 0.0, "{kind:?} at {x} {z} with density 0");
+                    spawned += 1;
+                }
+            }
+
+            #[cfg(feature = "hud")]
+            assert_eq!(detect_biome(x as f32, z as f32, &noise, &config), weights.dominant());
+        }
+        assert!(spawned > 0);
+    }
+
+    #[test]
+    fn snow_colour_follows_the_weights() {
+        let noise = NoiseGenerators::new(67);
+        let config = WorldGenConfig::default();
+        let map = BiomeMap::new(&noise, &config);
+        let coord = ChunkCoord { x: 2, z: -1 };
+        let mesh = calc_to_generate_chunk(coord, 0, &noise, &config, &TerrainEdits::default());
+
+        let (Some(VertexAttributeValues::Float32x3(positions)), Some(VertexAttributeValues::Float32x3(normals)), Some(VertexAttributeValues::Float32x4(colors))) = (
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+            mesh.attribute(Mesh::ATTRIBUTE_COLOR),
+        ) else {
+            panic!("chunk mesh without positions, normals or colours");
+        };
+
+        let stride = config.chunk_size + 1; // the skirts come after the grid
+        for i in 0..stride * stride {
+            let x = (coord.x as f32 * config.chunk_world_size() + positions[i][0]) as f64;
+            let z = (coord.z as f32 * config.chunk_world_size() + positions[i][2]) as f64;
+            let weights = map.weights(x, z);
+            // the snow only varies a little around the blended biome colour, red the least
+            for (c, variation) in [0.1, 0.15, 0.25].into_iter().enumerate() {
+                let base = weights.blend(|b| b.ground_color(normals[i][1], &config)[c]);
+                assert!((colors[i][c] - base).abs() <= variation + 1e-4, "colour {:?} at {x} {z}, biome colour {base}", colors[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn pure_biomes_ignore_the_other_biomes_settings() {
+        let noise = NoiseGenerators::new(67);
+        let config = WorldGenConfig::default();
+        let map = BiomeMap::new(&noise, &config);
+
+        for biome in Biome::ALL {
+            // same config, but every other biome gets silly values
+            let mut other = config.clone();
+            for b in Biome::ALL.into_iter().filter(|&b| b != biome) {
+                match b {
+                    Biome::Plains => { other.plains_scale = 50.0; other.plains_tree_density = 0.9; }
+                    Biome::Forest => { other.forest_scale = 50.0; other.forest_tree_density = 0.9; }
+                    Biome::FrozenLake => other.lake_level = 50.0,
+                    Biome::GlacialMountains => { other.mountain_height = 500.0; other.mountain_tree_density = 0.9; }
+                    Biome::Tundra => { other.tundra_scale = 50.0; other.shrub_density = 0.9; }
+                }
+            }
+
+            let pure: Vec<_> = sample_positions().filter(|&(x, z)| map.weights(x, z).get(biome) == 1.0).collect();
+            assert!(!pure.is_empty(), "no pure {biome:?} in the sample area");
+
+            for (x, z) in pure {
+                assert_eq!(map.biome_at(x, z), biome);
+                assert_eq!(get_height(x, z, &noise, &config), get_height(x, z, &noise, &other));
+                for kind in [PropKind::Tree, PropKind::CandyCane, PropKind::Shrub] {
+                    assert_eq!(prop_density(kind, x, z, &noise, &config), prop_density(kind, x, z, &noise, &other));
+                }
+            }
+        }
+    }
+}
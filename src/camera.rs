@@ -0,0 +1,245 @@
+use bevy::prelude::*;
+#[cfg(feature = "post-fx")]
+use bevy::core_pipeline::bloom::Bloom;
+use bevy::transform::TransformSystem;
+
+use crate::chunks::get_height;
+use crate::config::WorldGenConfig;
+use crate::input::{Action, ActionState};
+use crate::noise::NoiseGenerators;
+#[cfg(feature = "post-fx")]
+use crate::sky::SkyLight;
+#[cfg(feature = "post-fx")]
+use crate::weather::Weather;
+use crate::player::{FlyCamera, PlayerMode, SledEntity, SledMotion, PLAYER_EYE_HEIGHT};
+
+// the camera is its own entity now, it follows the player (FlyCamera) in one of three ways.
+// the player also got a body, so there is something to look at in third person
+
+const ORBIT_DISTANCE: f32 = 5.0;
+const CHASE_DISTANCE: f32 = 6.0;
+const CHASE_HEIGHT: f32 = 2.5;
+const CAMERA_CLEARANCE: f32 = 0.5; // minimum height above the terrain
+const SPRING_STIFFNESS: f32 = 60.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CameraMode {
+    #[default]
+    FirstPerson,
+    ThirdPerson, // orbits the player, mouse / right stick turns it
+    Chase, // sits behind the sled (or the player) and lags a bit
+}
+
+impl CameraMode {
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::FirstPerson => CameraMode::ThirdPerson,
+            CameraMode::ThirdPerson => CameraMode::Chase,
+            CameraMode::Chase => CameraMode::FirstPerson,
+        }
+    }
+}
+
+/// The camera entity. `velocity` is the state of the chase spring.
+#[derive(Component, Default)]
+pub struct CameraRig {
+    pub mode: CameraMode,
+    pub velocity: Vec3,
+}
+
+/// The visible player body, hidden in first person.
+#[derive(Component)]
+pub struct PlayerAvatar;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, (spawn_camera, spawn_avatar))
+            .add_systems(Update, cycle_camera_mode)
+            // after all the movement, before the transforms get propagated
+            .add_systems(PostUpdate, (follow_player, update_avatar).before(TransformSystem::TransformPropagate));
+
+        #[cfg(feature = "post-fx")]
+        app.add_systems(Startup, add_post_fx.after(spawn_camera))
+            .add_systems(Update, update_fog);
+    }
+}
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn((
+        Camera3d::default(),
+        CameraRig::default(),
+        Transform::default(),
+    ));
+}
+
+#[cfg(feature = "post-fx")]
+fn add_post_fx(mut commands: Commands, config: Res<WorldGenConfig>, camera_query: Query<Entity, With<CameraRig>>) {
+    for camera in &camera_query {
+        commands.entity(camera).insert((
+            Bloom::NATURAL,
+            DistanceFog {
+                color: Color::srgb(0.8, 0.9, 1.0),
+                falloff: fog_falloff(&config, 1.0),
+                ..default()
+            },
+        ));
+    }
+}
+
+// distance scales with the weather, a blizzard closes right in
+#[cfg(feature = "post-fx")]
+fn fog_falloff(config: &WorldGenConfig, weather_distance: f32) -> FogFalloff {
+    FogFalloff::Linear {
+        start: (config.render_distance as f32) * config.chunk_size as f32 * weather_distance,
+        end: (config.render_distance as f32 + 10.0) * config.chunk_size as f32 * weather_distance,
+    }
+}
+
+// render distance can change with the config file, the weather and the time of day change all the time
+#[cfg(feature = "post-fx")]
+fn update_fog(
+    config: Res<WorldGenConfig>,
+    weather: Res<Weather>,
+    sky_light: Res<SkyLight>,
+    mut fog_query: Query<&mut DistanceFog>,
+) {
+    let color = sky_light.fog;
+    for mut fog in &mut fog_query {
+        fog.falloff = fog_falloff(&config, weather.params.fog_distance);
+        fog.color = Color::srgb(color.x, color.y, color.z);
+    }
+}
+
+// capsule body with a head, the feet are at the origin
+fn spawn_avatar(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let jacket = materials.add(StandardMaterial {
+        base_color: Color::srgb(0.75, 0.12, 0.1),
+        perceptual_roughness: 0.9,
+        ..default()
+    });
+    let face = materials.add(StandardMaterial {
+        base_color: Color::srgb(0.95, 0.8, 0.7),
+        ..default()
+    });
+
+    commands.spawn((PlayerAvatar, Transform::default(), Visibility::Hidden))
+        .with_children(|body| {
+            body.spawn((
+                Mesh3d(meshes.add(Capsule3d::new(0.25, 0.5))),
+                MeshMaterial3d(jacket),
+                Transform::from_xyz(0.0, 0.5, 0.0),
+            ));
+            // eyes at PLAYER_EYE_HEIGHT
+            body.spawn((
+                Mesh3d(meshes.add(Sphere::new(0.18))),
+                MeshMaterial3d(face),
+                Transform::from_xyz(0.0, PLAYER_EYE_HEIGHT, 0.0),
+            ));
+        });
+}
+
+fn cycle_camera_mode(actions: Res<ActionState>, mut rigs: Query<&mut CameraRig>) {
+    if !actions.just_pressed(Action::CycleCamera) {
+        return;
+    }
+    for mut rig in &mut rigs {
+        rig.mode = rig.mode.next();
+        info!("camera: {:?}", rig.mode);
+    }
+}
+
+// walks from the focus towards the wanted camera position and stops before it goes into the ground
+fn clear_of_terrain(focus: Vec3, wanted: Vec3, noise: &NoiseGenerators, config: &WorldGenConfig) -> Vec3 {
+    let steps = 8;
+    let mut last = focus;
+    for i in 1..=steps {
+        let p = focus.lerp(wanted, i as f32 / steps as f32);
+        if p.y < get_height(p.x as f64, p.z as f64, noise, config) + CAMERA_CLEARANCE {
+            break;
+        }
+        last = p;
+    }
+    last
+}
+
+pub fn follow_player(
+    time: Res<Time>,
+    noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
+    mode: Res<State<PlayerMode>>,
+    player_query: Query<(&Transform, &FlyCamera), Without<CameraRig>>,
+    sled_query: Query<&SledMotion, With<SledEntity>>,
+    mut camera_query: Query<(&mut Transform, &mut CameraRig)>,
+) {
+    let Ok((player_t, player)) = player_query.single() else { return };
+    let Ok((mut camera_t, mut rig)) = camera_query.single_mut() else { return };
+    let dt = time.delta_secs();
+
+    // first person is just the player's eyes
+    if rig.mode == CameraMode::FirstPerson {
+        *camera_t = *player_t;
+        rig.velocity = Vec3::ZERO;
+        return;
+    }
+
+    let focus = player_t.translation;
+    let (wanted, spring) = match rig.mode {
+        CameraMode::ThirdPerson => {
+            let back = player_t.rotation * Vec3::Z;
+            (focus + back * ORBIT_DISTANCE, false)
+        }
+        _ => {
+            // behind the way the sled points, on foot behind where the player looks
+            let heading = match sled_query.single() {
+                Ok(motion) if *mode.get() == PlayerMode::Sledding => motion.heading,
+                _ => player.yaw,
+            };
+            let back = Quat::from_rotation_y(heading) * Vec3::Z;
+            (focus + back * CHASE_DISTANCE + Vec3::Y * CHASE_HEIGHT, true)
+        }
+    };
+    let wanted = clear_of_terrain(focus, wanted, &noise, &config);
+
+    if spring {
+        // critically damped spring, lags behind at speed and never overshoots
+        let damping = 2.0 * SPRING_STIFFNESS.sqrt();
+        let pull = (wanted - camera_t.translation) * SPRING_STIFFNESS - rig.velocity * damping;
+        rig.velocity += pull * dt;
+        camera_t.translation += rig.velocity * dt;
+        // if the camera fell far behind (teleport, loading a save) jump there
+        if camera_t.translation.distance(wanted) > CHASE_DISTANCE * 4.0 {
+            camera_t.translation = wanted;
+            rig.velocity = Vec3::ZERO;
+        }
+    } else {
+        camera_t.translation = wanted;
+        rig.velocity = Vec3::ZERO;
+    }
+
+    // the spring can still dip into a valley
+    let ground = get_height(camera_t.translation.x as f64, camera_t.translation.z as f64, &noise, &config);
+    camera_t.translation.y = camera_t.translation.y.max(ground + CAMERA_CLEARANCE);
+    camera_t.look_at(focus, Vec3::Y);
+}
+
+// stands below the player's eyes, only turns with the yaw
+fn update_avatar(
+    player_query: Query<(&Transform, &FlyCamera), Without<PlayerAvatar>>,
+    camera_query: Query<&CameraRig>,
+    mut avatar_query: Query<(&mut Transform, &mut Visibility), With<PlayerAvatar>>,
+) {
+    let Ok((player_t, player)) = player_query.single() else { return };
+    let Ok((mut avatar_t, mut visibility)) = avatar_query.single_mut() else { return };
+
+    avatar_t.translation = player_t.translation - Vec3::Y * PLAYER_EYE_HEIGHT;
+    avatar_t.rotation = Quat::from_rotation_y(player.yaw);
+
+    let first_person = camera_query.single().is_ok_and(|rig| rig.mode == CameraMode::FirstPerson);
+    visibility.set_if_neq(if first_person { Visibility::Hidden } else { Visibility::Inherited });
+}
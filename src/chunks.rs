@@ -7,39 +7,70 @@ use bevy::render::render_asset::RenderAssetUsages;
 
 use crate::world_gen::*; // link to world gen module
 use crate::noise::NoiseGenerators;
+use crate::config::WorldGenConfig;
+use crate::biome::{BiomeMap, Biome, PropKind};
+use crate::deform::TerrainEdits;
 
-// biome shit
-pub const BIOME_FREQ: f64 = 0.008;
+// the ice sits a bit above the terrain so they don't z-fight
+pub const ICE_OFFSET: f32 = 0.05;
 
-pub const PLAINS_SCALE: f32 = 2.0;
-pub const FOREST_SCALE: f32 = 1.0;
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum Biome {
-    Plains,
-    Forest,
+// vertex step for a lod level, lod 0 = full resolution
+pub fn lod_step(lod: u32) -> usize {
+    1 << lod
 }
 
-pub fn calc_to_generate_chunk(coord: ChunkCoord, noise: &NoiseGenerators,) -> Mesh {
+pub fn calc_to_generate_chunk(
+    coord: ChunkCoord,
+    lod: u32,
+    noise: &NoiseGenerators,
+    config: &WorldGenConfig,
+    edits: &TerrainEdits,
+) -> Mesh {
     let mut positions = Vec::new();
+    let mut normals = Vec::new();
     let mut colors: Vec<[f32; 4]> = Vec::new();
     let mut indices: Vec<u32> = Vec::new();
 
-    let stride = CHUNK_SIZE + 1;
-
-    for z in 0..=CHUNK_SIZE { // calc vertices
-        for x in 0..=CHUNK_SIZE {
-            let world_x = ((coord.x * CHUNK_SIZE as i32 + x as i32) as f64) * VERTEX_SPACING as f64;
-            let world_z = ((coord.z * CHUNK_SIZE as i32 + z as i32) as f64) * VERTEX_SPACING as f64;
+    let step = lod_step(lod);
+    let chunk_size = config.chunk_size as i32;
+    let cells = config.chunk_size / step; // quads per side at this lod
+    let stride = cells + 1;
+    let spacing = config.vertex_spacing * step as f32;
+
+    let world_pos = |x: i32, z: i32| {
+        let world_x = ((coord.x * chunk_size + x * step as i32) as f64) * config.vertex_spacing as f64;
+        let world_z = ((coord.z * chunk_size + z * step as i32) as f64) * config.vertex_spacing as f64;
+        (world_x, world_z)
+    };
+
+    // heights with a 1 vertex border, so normals at the chunk edge match the neighbour
+    let padded = stride + 2;
+    let mut heights = vec![0.0_f32; padded * padded];
+    for z in -1..=stride as i32 {
+        for x in -1..=stride as i32 {
+            let (world_x, world_z) = world_pos(x, z);
+            heights[(z + 1) as usize * padded + (x + 1) as usize] = get_surface_height(world_x, world_z, noise, config, edits);
+        }
+    }
+    let height_at = |x: i32, z: i32| heights[(z + 1) as usize * padded + (x + 1) as usize];
+    let biomes = BiomeMap::new(noise, config);
 
-            let height = get_height(world_x, world_z, noise);
+    for z in 0..stride as i32 { // calc vertices
+        for x in 0..stride as i32 {
+            let (world_x, world_z) = world_pos(x, z);
+            let height = height_at(x, z);
 
             positions.push([
-                x as f32 * VERTEX_SPACING,
+                x as f32 * spacing,
                 height,
-                z as f32 * VERTEX_SPACING,
+                z as f32 * spacing,
             ]);
 
+            let dhdx = (height_at(x + 1, z) - height_at(x - 1, z)) / (2.0 * spacing);
+            let dhdz = (height_at(x, z + 1) - height_at(x, z - 1)) / (2.0 * spacing);
+            let normal = Vec3::new(-dhdx, 1.0, -dhdz).normalize();
+            normals.push(normal.to_array());
+
             // // height spcifc colour of snow
             // let h_norm = (height / 30.0).clamp(0.0, 1.0);
 
@@ -58,21 +89,24 @@ pub fn calc_to_generate_chunk(coord: ChunkCoord, noise: &NoiseGenerators,) -> Me
 
             // colors.push(color);
             
-            // einfach so ein bisschen variation
+            // biome snow colour plus einfach so ein bisschen variation
+            let weights = biomes.weights(world_x, world_z);
+            let base = [0, 1, 2].map(|i| weights.blend(|biome| biome.ground_color(normal.y, config)[i]));
+
             let n = noise.height.get([world_x * 0.15, world_z * 0.15]) as f32;
 
             let variation = n * 0.25;
 
-            let r = (0.88 + variation * 0.4).clamp(0.0, 1.0);
-            let g = (0.93 + variation * 0.6).clamp(0.0, 1.0);
-            let b = (1.00 + variation * 1.0).clamp(0.0, 1.0);
+            let r = (base[0] + variation * 0.4).clamp(0.0, 1.0);
+            let g = (base[1] + variation * 0.6).clamp(0.0, 1.0);
+            let b = (base[2] + variation * 1.0).clamp(0.0, 1.0);
 
             colors.push([r, g, b, 1.0]);
         }
     }
 
-    for z in 0..CHUNK_SIZE { // calc indices (triangles connecting vertices)
-        for x in 0..CHUNK_SIZE {
+    for z in 0..cells { // calc indices (triangles connecting vertices)
+        for x in 0..cells {
             let i = z * stride + x;
 
             indices.extend_from_slice(&[
@@ -86,55 +120,123 @@ pub fn calc_to_generate_chunk(coord: ChunkCoord, noise: &NoiseGenerators,) -> Me
         }
     }
 
+    // skirts: a strip hanging down from every edge, hides the cracks between chunks of different lod
+    let skirt_depth = spacing * 2.0;
+    let mut border = Vec::new(); // walked clockwise (seen from above) so the skirts face outwards
+    for x in 0..cells { border.push(x); }
+    for z in 0..cells { border.push(z * stride + cells); }
+    for x in (1..=cells).rev() { border.push(cells * stride + x); }
+    for z in (1..=cells).rev() { border.push(z * stride); }
+    border.push(0);
+
+    let first_skirt = positions.len();
+    for &i in &border {
+        let [x, y, z] = positions[i];
+        positions.push([x, y - skirt_depth, z]);
+        normals.push(normals[i]);
+        colors.push(colors[i]);
+    }
+    for k in 0..border.len() - 1 {
+        let a = border[k] as u32;
+        let b = border[k + 1] as u32;
+        let a_low = (first_skirt + k) as u32;
+        let b_low = (first_skirt + k + 1) as u32;
+
+        indices.extend_from_slice(&[a, b, a_low, b, b_low, a_low]);
+    }
+
     let mut mesh = Mesh::new(
         PrimitiveTopology::TriangleList,
         RenderAssetUsages::default(),
     );
 
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
     mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
     mesh.insert_indices(Indices::U32(indices));
-    mesh.compute_normals();
 
     mesh
 }
 
 
-pub fn get_height(world_x: f64, world_z: f64, noise: &NoiseGenerators) -> f32 {
+pub fn get_height(world_x: f64, world_z: f64, noise: &NoiseGenerators, config: &WorldGenConfig) -> f32 {
     // calc für biomes
-    let biome_val = noise.biome.get([world_x * BIOME_FREQ, world_z * BIOME_FREQ]) as f32;
+    let weights = BiomeMap::new(noise, config).weights(world_x, world_z);
 
-    // biome blend factor t (0 = plains, 1 = forest)
-    let t = ((biome_val + 0.2) / (0.3 + 0.2)).clamp(0.0, 1.0);
+    // final smooth height, every biome has its own height function
+    weights.blend(|biome| biome.height(world_x, world_z, noise, config))
+}
 
-    // base height from noise
-    let base_h = noise.height.get([world_x * NOISE_FREQ, world_z * NOISE_FREQ]) as f32;
+// the height the player actually sees, noise height plus footprints, tracks and craters
+pub fn get_surface_height(world_x: f64, world_z: f64, noise: &NoiseGenerators, config: &WorldGenConfig, edits: &TerrainEdits) -> f32 {
+    get_height(world_x, world_z, noise, config) + edits.offset_at(world_x, world_z, config)
+}
 
-    // height per biome
-    let plains_h = base_h * NOISE_AMP * PLAINS_SCALE;
-    let forest_h = base_h * NOISE_AMP * FOREST_SCALE;
+// ice sheet over the frozen lake parts of a chunk, None if there's no lake in it.
+// it lies on get_height like the player does, so near the shore it follows the blended ground
+pub fn calc_ice_mesh(coord: ChunkCoord, lod: u32, noise: &NoiseGenerators, config: &WorldGenConfig) -> Option<Mesh> {
+    let step = lod_step(lod);
+    let cells = config.chunk_size / step;
+    let stride = cells + 1;
+    let spacing = config.vertex_spacing * step as f32;
+    let biomes = BiomeMap::new(noise, config);
+    let world_pos = |x: f32, z: f32| {
+        let world_x = (coord.x as f32 * config.chunk_world_size() + x * spacing) as f64;
+        let world_z = (coord.z as f32 * config.chunk_world_size() + z * spacing) as f64;
+        (world_x, world_z)
+    };
+
+    // check the middle of every cell, same 0.5 as on_ice in the player
+    let lake_cells: Vec<(usize, usize)> = (0..cells)
+        .flat_map(|z| (0..cells).map(move |x| (x, z)))
+        .filter(|&(x, z)| {
+            let (world_x, world_z) = world_pos(x as f32 + 0.5, z as f32 + 0.5);
+            biomes.weights(world_x, world_z).get(Biome::FrozenLake) >= 0.5
+        })
+        .collect();
+    if lake_cells.is_empty() {
+        return None;
+    }
 
-    // final smooth height
-    let mut height = plains_h * (1.0 - t) + forest_h * t;
+    let mut heights = vec![0.0_f32; stride * stride];
+    for z in 0..stride {
+        for x in 0..stride {
+            let (world_x, world_z) = world_pos(x as f32, z as f32);
+            heights[z * stride + x] = get_height(world_x, world_z, noise, config) + ICE_OFFSET;
+        }
+    }
 
-    let erosion = noise.height.get([world_x * 0.03, world_z * 0.03]) as f32 * 2.0;
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    for (x, z) in lake_cells {
+        let i = positions.len() as u32;
+        let (x0, z0) = (x as f32 * spacing, z as f32 * spacing);
+        let h = |dx: usize, dz: usize| heights[(z + dz) * stride + x + dx];
+        positions.extend_from_slice(&[
+            [x0, h(0, 0), z0],
+            [x0 + spacing, h(1, 0), z0],
+            [x0, h(0, 1), z0 + spacing],
+            [x0 + spacing, h(1, 1), z0 + spacing],
+        ]);
+        indices.extend_from_slice(&[i, i + 2, i + 1, i + 1, i + 2, i + 3]);
+    }
 
-    // rounded snow drifts
-    let drift_n = noise.height.get([world_x * 0.01, world_z * 0.01]) as f32;
-    let drifts = drift_n.abs().powf(2.0) * 6.0;
+    let normals = vec![[0.0, 1.0, 0.0]; positions.len()];
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default());
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_indices(Indices::U32(indices));
 
-    height += erosion + drifts;
-    // returns height
-    height
+    Some(mesh)
 }
 
-pub fn get_surface_normal(world_x: f64, world_z: f64, noise: &NoiseGenerators) -> Vec3 {
-    let e = VERTEX_SPACING as f64; // sample step in world units
+pub fn get_surface_normal(world_x: f64, world_z: f64, noise: &NoiseGenerators, config: &WorldGenConfig, edits: &TerrainEdits) -> Vec3 {
+    let e = config.vertex_spacing as f64; // sample step in world units
 
-    let h_l = get_height(world_x - e, world_z, noise);
-    let h_r = get_height(world_x + e, world_z, noise);
-    let h_d = get_height(world_x, world_z - e, noise);
-    let h_u = get_height(world_x, world_z + e, noise);
+    let h_l = get_surface_height(world_x - e, world_z, noise, config, edits);
+    let h_r = get_surface_height(world_x + e, world_z, noise, config, edits);
+    let h_d = get_surface_height(world_x, world_z - e, noise, config, edits);
+    let h_u = get_surface_height(world_x, world_z + e, noise, config, edits);
 
     let dhdx = (h_r - h_l) / (2.0 * e as f32);
     let dhdz = (h_u - h_d) / (2.0 * e as f32);
@@ -143,30 +245,68 @@ pub fn get_surface_normal(world_x: f64, world_z: f64, noise: &NoiseGenerators) -
     Vec3::new(-dhdx, 1.0, -dhdz).normalize()
 }
 
+// blended prop density of the biomes at this position
+pub fn prop_density(kind: PropKind, world_x: f64, world_z: f64, noise: &NoiseGenerators, config: &WorldGenConfig) -> f32 {
+    BiomeMap::new(noise, config)
+        .weights(world_x, world_z)
+        .blend(|biome| biome.prop_density(kind, config))
+}
+
 pub fn should_tree_spawn(
     world_x: f64,
     world_z: f64,
     noise: &NoiseGenerators,
+    config: &WorldGenConfig,
 ) -> bool {
-    let biome_val = noise.biome.get([world_x * BIOME_FREQ, world_z * BIOME_FREQ]) as f32;
-   
-    let t = ((biome_val + 0.2) / 0.5).clamp(0.0, 1.0);
-
-    let tree_frequency = lerp(0.03, 0.25, t);
+    let tree_frequency = prop_density(PropKind::Tree, world_x, world_z, noise, config);
 
     let tree_noise = noise.tree.get([world_x * 0.14, world_z * 0.14]) as f32; // how close they spawn together somewhere here
     let tree_noise = (tree_noise + 1.0) * 0.5;
 
     tree_noise < tree_frequency
 }
-// linear interpolation helper von chatty
-fn lerp(a: f32, b: f32, t: f32) -> f32 {
-    a * (1.0 - t) + b * t
-}
 // check if candy caner should spawn
-pub fn should_candy_spawn(world_x: f64, world_z: f64, noise: &NoiseGenerators) -> bool {
+pub fn should_candy_spawn(world_x: f64, world_z: f64, noise: &NoiseGenerators, config: &WorldGenConfig) -> bool {
     let n = noise.candy_cane.get([world_x * 0.30, world_z * 0.30]) as f32;
     let n = (n + 1.0) * 0.5;
 
-    n < 0.05 // spawn density
-}
\ No newline at end of file
+    n < prop_density(PropKind::CandyCane, world_x, world_z, noise, config) // spawn density
+}
+
+// low shrubs, the tundra has these instead of pines
+pub fn should_shrub_spawn(world_x: f64, world_z: f64, noise: &NoiseGenerators, config: &WorldGenConfig) -> bool {
+    let n = noise.shrub.get([world_x * 0.25, world_z * 0.25]) as f32;
+    let n = (n + 1.0) * 0.5;
+
+    n < prop_density(PropKind::Shrub, world_x, world_z, noise, config)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    #[test]
+    fn ice_lies_on_the_terrain() {
+        let noise = NoiseGenerators::new(67);
+        let config = WorldGenConfig::default();
+        let coords = (-12..12).flat_map(|x| (-12..12).map(move |z| ChunkCoord { x, z }));
+
+        let mut lakes = 0;
+        for coord in coords {
+            for lod in [0, 2] {
+                let Some(ice) = calc_ice_mesh(coord, lod, &noise, &config) else { continue };
+                let Some(VertexAttributeValues::Float32x3(positions)) = ice.attribute(Mesh::ATTRIBUTE_POSITION) else {
+                    panic!("ice mesh without positions");
+                };
+                lakes += 1;
+
+                let (origin_x, origin_z) = (coord.x as f32 * config.chunk_world_size(), coord.z as f32 * config.chunk_world_size());
+                for [x, y, z] in positions {
+                    let ground = get_height((origin_x + x) as f64, (origin_z + z) as f64, &noise, &config);
+                    assert!((y - ICE_OFFSET - ground).abs() < 1e-3, "ice at {y}, ground at {ground} in {coord:?}");
+                }
+            }
+        }
+        assert!(lakes > 0, "no frozen lake in the sample area");
+    }
+}
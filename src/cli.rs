@@ -0,0 +1,199 @@
+use std::path::PathBuf;
+use bevy::prelude::*;
+use thiserror::Error;
+
+// launch options from the command line, every option can also come from a WINTER_* environment variable.
+// flags win over the environment
+
+pub const DEFAULT_SEED: u32 = 67;
+
+pub const USAGE: &str = "usage: bevy_winter [--seed SEED] [--render-distance CHUNKS] [--spawn X,Z] [--time HOURS] [--fullscreen] [--no-snow] [--headless]
+  SEED can be a number or any text, text gets hashed
+  HOURS is the time of day to start at, 0 to 24
+  environment: WINTER_SEED, WINTER_RENDER_DISTANCE, WINTER_SPAWN, WINTER_TIME, WINTER_FULLSCREEN, WINTER_NO_SNOW, WINTER_HEADLESS";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CliError {
+    #[error("{USAGE}")]
+    Help, // --help, not really an error
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl From<String> for CliError {
+    fn from(msg: String) -> Self {
+        CliError::Invalid(msg)
+    }
+}
+
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct LaunchOptions {
+    pub seed: u32,
+    pub seed_given: bool, // false = default seed, the normal save slot is used
+    pub render_distance: Option<i32>,
+    pub spawn: Option<Vec2>,
+    pub time: Option<f32>, // hour of the day at the start
+    pub fullscreen: bool,
+    pub snow: bool,
+    pub headless: bool,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            seed_given: false,
+            render_distance: None,
+            spawn: None,
+            time: None,
+            fullscreen: false,
+            snow: true,
+            headless: false,
+        }
+    }
+}
+
+/// Numbers are used as they are, anything else gets hashed (FNV-1a), so "winter" is always the same world.
+pub fn seed_from_str(value: &str) -> u32 {
+    if let Ok(seed) = value.trim().parse::<u32>() {
+        return seed;
+    }
+    value.bytes().fold(0x811C_9DC5_u32, |hash, b| (hash ^ b as u32).wrapping_mul(0x0100_0193))
+}
+
+fn parse_spawn(value: &str) -> Result<Vec2, String> {
+    let (x, z) = value.split_once(',').ok_or_else(|| format!("spawn has to be X,Z but got {value}"))?;
+    let parse = |v: &str| v.trim().parse::<f32>().map_err(|e| format!("spawn {v}: {e}"));
+    Ok(Vec2::new(parse(x)?, parse(z)?))
+}
+
+fn parse_render_distance(value: &str) -> Result<i32, String> {
+    match value.trim().parse::<i32>() {
+        Ok(rd) if rd >= 0 => Ok(rd),
+        _ => Err(format!("render distance has to be a number of chunks, got {value}")),
+    }
+}
+
+fn parse_time(value: &str) -> Result<f32, String> {
+    match value.trim().parse::<f32>() {
+        Ok(hours) if (0.0..=24.0).contains(&hours) => Ok(hours),
+        _ => Err(format!("time has to be an hour from 0 to 24, got {value}")),
+    }
+}
+
+// "1", "true", "yes" and "on" turn a switch on
+fn env_switch(value: &str) -> bool {
+    matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on")
+}
+
+impl LaunchOptions {
+    /// Reads the real command line and environment.
+    pub fn from_env() -> Result<Self, CliError> {
+        Self::parse(std::env::args().skip(1), |key| std::env::var(key).ok())
+    }
+
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, CliError> {
+        let mut options = LaunchOptions::default();
+
+        if let Some(seed) = env("WINTER_SEED") {
+            options.seed = seed_from_str(&seed);
+            options.seed_given = true;
+        }
+        if let Some(rd) = env("WINTER_RENDER_DISTANCE") {
+            options.render_distance = Some(parse_render_distance(&rd)?);
+        }
+        if let Some(spawn) = env("WINTER_SPAWN") {
+            options.spawn = Some(parse_spawn(&spawn)?);
+        }
+        if let Some(time) = env("WINTER_TIME") {
+            options.time = Some(parse_time(&time)?);
+        }
+        options.fullscreen = env("WINTER_FULLSCREEN").is_some_and(|v| env_switch(&v));
+        options.snow = !env("WINTER_NO_SNOW").is_some_and(|v| env_switch(&v));
+        options.headless = env("WINTER_HEADLESS").is_some_and(|v| env_switch(&v));
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // --flag=value works too
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || inline_value.clone().or_else(|| args.next()).ok_or_else(|| format!("{flag} needs a value"));
+
+            match flag.as_str() {
+                "--seed" => {
+                    options.seed = seed_from_str(&value()?);
+                    options.seed_given = true;
+                }
+                "--render-distance" => options.render_distance = Some(parse_render_distance(&value()?)?),
+                "--spawn" => options.spawn = Some(parse_spawn(&value()?)?),
+                "--time" => options.time = Some(parse_time(&value()?)?),
+                "--fullscreen" => options.fullscreen = true,
+                "--no-snow" => options.snow = false,
+                "--headless" => options.headless = true,
+                "--help" | "-h" => return Err(CliError::Help),
+                _ => return Err(CliError::Invalid(format!("unknown option {flag}\n{USAGE}"))),
+            }
+        }
+
+        Ok(options)
+    }
+
+    // a world started with an explicit seed gets its own save, so saves never mix seeds
+    pub fn save_dir(&self) -> Option<PathBuf> {
+        self.seed_given.then(|| PathBuf::from(format!("saves/seed-{}", self.seed)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<LaunchOptions, CliError> {
+        let env: Vec<(String, String)> = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        LaunchOptions::parse(
+            args.iter().map(|a| a.to_string()),
+            |key| env.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone()),
+        )
+    }
+
+    #[test]
+    fn flags_are_parsed() {
+        let options = parse(
+            &["--seed", "42", "--render-distance=6", "--spawn", "10.5,-3", "--time", "21.5", "--fullscreen", "--no-snow", "--headless"],
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(options.seed, 42);
+        assert!(options.seed_given);
+        assert_eq!(options.render_distance, Some(6));
+        assert_eq!(options.spawn, Some(Vec2::new(10.5, -3.0)));
+        assert_eq!(options.time, Some(21.5));
+        assert!(options.fullscreen && !options.snow && options.headless);
+    }
+
+    #[test]
+    fn text_seeds_are_hashed_the_same_every_time() {
+        assert_eq!(seed_from_str("winter"), seed_from_str("winter"));
+        assert_ne!(seed_from_str("winter"), seed_from_str("summer"));
+        assert_eq!(seed_from_str("67"), 67);
+    }
+
+    #[test]
+    fn flags_win_over_the_environment() {
+        let options = parse(&["--seed", "5"], &[("WINTER_SEED", "9"), ("WINTER_NO_SNOW", "1")]).unwrap();
+        assert_eq!(options.seed, 5);
+        assert!(!options.snow);
+
+        assert_eq!(parse(&[], &[]).unwrap(), LaunchOptions::default());
+        assert!(parse(&["--render-distance", "-1"], &[]).is_err());
+        assert!(parse(&["--bogus"], &[]).is_err());
+        assert!(parse(&["--time", "25"], &[]).is_err());
+        assert_eq!(parse(&["--seed", "5", "--help"], &[]), Err(CliError::Help));
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+
+use crate::biome::PropKind;
+use crate::config::WorldGenConfig;
+use crate::world_gen::{ChunkCoord, PropPlacement};
+
+// lightweight prop collision: every prop is an upright cylinder, stored per chunk
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PropCollider {
+    pub kind: PropKind,
+    pub base: Vec3, // bottom centre in world space
+    pub radius: f32,
+    pub height: f32,
+}
+
+/// Result of pushing a body out of the props it overlaps.
+#[derive(Clone, Copy, Debug)]
+pub struct Contact {
+    pub push: Vec3, // horizontal correction to apply to the body
+    pub normal: Vec3, // horizontal direction away from the prop that was hit
+}
+
+/// Colliders of all loaded chunks, world_gen adds and removes them together with the chunks.
+#[derive(Resource, Default)]
+pub struct ColliderGrid {
+    pub cells: HashMap<ChunkCoord, Vec<PropCollider>>,
+}
+
+// collider for one prop placement, chunk_origin is the world position of the chunk
+pub fn prop_collider(prop: &PropPlacement, chunk_origin: Vec3) -> PropCollider {
+    let t = &prop.transform;
+    // rough sizes of the models at scale 1
+    let (radius, height) = match prop.kind {
+        PropKind::Tree => (0.5, 4.0),
+        PropKind::CandyCane => (0.06, 0.5),
+        PropKind::Shrub => (0.6, 0.6),
+    };
+
+    PropCollider {
+        kind: prop.kind,
+        base: chunk_origin + t.translation,
+        radius: radius * t.scale.x,
+        height: height * t.scale.y,
+    }
+}
+
+impl ColliderGrid {
+    // colliders in the chunk of pos and the 8 around it (props near a border can reach over)
+    fn nearby<'a>(&'a self, pos: Vec3, config: &WorldGenConfig) -> impl Iterator<Item = &'a PropCollider> {
+        let center = config.chunk_coord(pos);
+        (-1..=1)
+            .flat_map(move |dx| (-1..=1).map(move |dz| ChunkCoord { x: center.x + dx, z: center.z + dz }))
+            .filter_map(|coord| self.cells.get(&coord))
+            .flatten()
+    }
+
+    /// Pushes an upright body (bottom point, height, radius) out of every prop it overlaps.
+    pub fn resolve(&self, bottom: Vec3, height: f32, radius: f32, config: &WorldGenConfig) -> Option<Contact> {
+        let mut push = Vec3::ZERO;
+        let mut normal = Vec3::ZERO;
+
+        for collider in self.nearby(bottom, config) {
+            // no vertical overlap
+            if bottom.y > collider.base.y + collider.height || bottom.y + height < collider.base.y {
+                continue;
+            }
+
+            let offset = (bottom + push - collider.base).with_y(0.0);
+            let min_dist = collider.radius + radius;
+            let dist = offset.length();
+            if dist >= min_dist {
+                continue;
+            }
+
+            // standing exactly in the middle, just pick a direction
+            let dir = if dist > 1e-4 { offset / dist } else { Vec3::X };
+            push += dir * (min_dist - dist);
+            normal += dir;
+        }
+
+        (push != Vec3::ZERO).then(|| Contact { push, normal: normal.normalize_or(Vec3::X) })
+    }
+
+    /// The prop a small ball at pos touches, with the horizontal normal where it hit.
+    pub fn hit(&self, pos: Vec3, radius: f32, config: &WorldGenConfig) -> Option<(PropCollider, Vec3)> {
+        self.nearby(pos, config)
+            .find(|c| {
+                pos.y >= c.base.y && pos.y <= c.base.y + c.height
+                    && (pos - c.base).with_y(0.0).length() < c.radius + radius
+            })
+            .map(|c| (*c, (pos - c.base).with_y(0.0).normalize_or(Vec3::X)))
+    }
+}
@@ -0,0 +1,251 @@
+use bevy::prelude::*;
+use bevy::asset::{io::Reader, AssetLoader, LoadContext};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::world_gen::{ChunkCoord, LOD_RINGS};
+use crate::chunks::lod_step;
+use crate::asset_paths::AssetPaths;
+
+pub const WORLD_GEN_CONFIG_PATH: &str = "world.worldgen.ron";
+
+/// All the tuning knobs for terrain, props and snow.
+/// Loaded from `assets/world.worldgen.ron`, missing fields fall back to the defaults below.
+#[derive(Asset, TypePath, Resource, Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldGenConfig {
+    pub chunk_size: usize,
+    pub vertex_spacing: f32, // abstand zwischen den vertices
+    pub render_distance: i32,
+    pub noise_freq: f64, // wie hart die übergänge sind
+    pub noise_amp: f32, // wie steil alles ist, also berge und so
+    pub biome_freq: f64,
+    pub plains_scale: f32,
+    pub forest_scale: f32,
+    pub tundra_scale: f32,
+    pub lake_level: f32, // height of the ice on frozen lakes
+    pub mountain_height: f32,
+    pub rock_slope: f32, // normal y below which glacier slopes show rock
+    pub plains_tree_density: f32,
+    pub forest_tree_density: f32,
+    pub mountain_tree_density: f32,
+    pub shrub_density: f32,
+    pub candy_density: f32,
+    pub snow_radius: f32,
+    pub snow_per_second: f32,
+    pub snow_spawn_height: f32,
+    pub snow_max_flakes: usize, // flakes that can fall at the same time
+    pub wind_strength: f32, // metres per second
+    pub wind_direction: f32, // degrees the wind blows to, 0 = north, 90 = east
+    pub wind_gustiness: f32, // gusts add up to this much on top (1 = double)
+    pub day_length: f32, // real seconds for 24 hours, 0 stops the clock
+}
+
+impl Default for WorldGenConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 32,
+            vertex_spacing: 3.0,
+            render_distance: 10,
+            noise_freq: 0.01,
+            noise_amp: 10.0,
+            biome_freq: 0.008,
+            plains_scale: 2.0,
+            forest_scale: 1.0,
+            tundra_scale: 0.6,
+            lake_level: -4.0,
+            mountain_height: 45.0,
+            rock_slope: 0.8,
+            plains_tree_density: 0.03,
+            forest_tree_density: 0.25,
+            mountain_tree_density: 0.02,
+            shrub_density: 0.12,
+            candy_density: 0.05,
+            snow_radius: 40.0,
+            snow_per_second: 2400.0,
+            snow_spawn_height: 2.0,
+            snow_max_flakes: 8192,
+            wind_strength: 4.0,
+            wind_direction: 60.0,
+            wind_gustiness: 0.8,
+            day_length: 1200.0,
+        }
+    }
+}
+
+impl WorldGenConfig {
+    /// Parses and checks a config file, used by the asset loader and the worldgen tool.
+    pub fn from_ron(bytes: &[u8]) -> Result<Self, WorldGenConfigError> {
+        let config: WorldGenConfig = ron::de::from_bytes(bytes)?;
+
+        // the coarsest lod has to fit into a chunk
+        let max_step = lod_step(LOD_RINGS.len() as u32);
+        if config.chunk_size == 0 || !config.chunk_size.is_multiple_of(max_step) {
+            return Err(WorldGenConfigError::ChunkSize(max_step));
+        }
+
+        Ok(config)
+    }
+
+    /// Whether the terrain would come out different: heights, biomes, ice and props.
+    /// Snow, wind, the clock and the render distance don't need new chunks.
+    pub fn terrain_differs(&self, other: &Self) -> bool {
+        self.grid_differs(other)
+            || self.noise_freq != other.noise_freq
+            || self.noise_amp != other.noise_amp
+            || self.biome_freq != other.biome_freq
+            || self.plains_scale != other.plains_scale
+            || self.forest_scale != other.forest_scale
+            || self.tundra_scale != other.tundra_scale
+            || self.lake_level != other.lake_level
+            || self.mountain_height != other.mountain_height
+            || self.rock_slope != other.rock_slope
+            || self.plains_tree_density != other.plains_tree_density
+            || self.forest_tree_density != other.forest_tree_density
+            || self.mountain_tree_density != other.mountain_tree_density
+            || self.shrub_density != other.shrub_density
+            || self.candy_density != other.candy_density
+    }
+
+    // the terrain edits live on this grid, they don't line up anymore when it changes
+    pub fn grid_differs(&self, other: &Self) -> bool {
+        self.chunk_size != other.chunk_size || self.vertex_spacing != other.vertex_spacing
+    }
+
+    // size of one chunk in world units
+    pub fn chunk_world_size(&self) -> f32 {
+        self.chunk_size as f32 * self.vertex_spacing
+    }
+
+    pub fn chunk_coord(&self, pos: Vec3) -> ChunkCoord {
+        ChunkCoord {
+            x: (pos.x / self.chunk_world_size()).floor() as i32,
+            z: (pos.z / self.chunk_world_size()).floor() as i32,
+        }
+    }
+}
+
+/// Settings from the command line that win over the config file, also after a hot reload.
+#[derive(Resource, Default, Clone, Debug)]
+pub struct ConfigOverrides {
+    pub render_distance: Option<i32>,
+}
+
+impl ConfigOverrides {
+    pub fn apply(&self, config: &mut WorldGenConfig) {
+        if let Some(render_distance) = self.render_distance {
+            config.render_distance = render_distance;
+        }
+    }
+}
+
+/// Sent when a new config changes the terrain (see terrain_differs), the chunks, the saved regions and the snow ground listen to it.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct TerrainChanged {
+    pub grid: bool, // chunk size or vertex spacing, the terrain edits don't fit anymore
+}
+
+#[derive(Resource)]
+pub struct WorldGenConfigHandle(pub Handle<WorldGenConfig>);
+
+pub struct WorldGenConfigPlugin;
+
+impl Plugin for WorldGenConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldGenConfig>()
+            .init_resource::<ConfigOverrides>()
+            .init_resource::<AssetPaths>()
+            .init_asset::<WorldGenConfig>()
+            .init_asset_loader::<WorldGenConfigLoader>()
+            .add_event::<TerrainChanged>()
+            .add_systems(Startup, (load_config, apply_overrides))
+            .add_systems(PreUpdate, apply_config);
+    }
+}
+
+fn load_config(mut commands: Commands, asset_server: Res<AssetServer>, paths: Res<AssetPaths>) {
+    commands.insert_resource(WorldGenConfigHandle(asset_server.load(&paths.world_config)));
+}
+
+// the defaults are used until the file is loaded, those get the overrides too
+fn apply_overrides(overrides: Res<ConfigOverrides>, mut config: ResMut<WorldGenConfig>) {
+    overrides.apply(&mut config);
+}
+
+// copies the asset into the resource when it's loaded or the file changed (hot reload)
+fn apply_config(
+    mut events: EventReader<AssetEvent<WorldGenConfig>>,
+    handle: Option<Res<WorldGenConfigHandle>>,
+    assets: Res<Assets<WorldGenConfig>>,
+    overrides: Res<ConfigOverrides>,
+    mut config: ResMut<WorldGenConfig>,
+    mut terrain_changed: EventWriter<TerrainChanged>,
+) {
+    let Some(handle) = handle else { return };
+
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) && !event.is_modified(&handle.0) {
+            continue;
+        }
+        if let Some(new_config) = assets.get(&handle.0) {
+            let previous = std::mem::replace(&mut *config, new_config.clone());
+            overrides.apply(&mut config);
+            if config.terrain_differs(&previous) {
+                terrain_changed.write(TerrainChanged { grid: config.grid_differs(&previous) });
+            }
+            info!("world gen config loaded");
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum WorldGenConfigError {
+    #[error("could not read world gen config: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse world gen config: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("chunk_size has to be a multiple of {0} for the lod levels")]
+    ChunkSize(usize),
+}
+
+#[derive(Default)]
+pub struct WorldGenConfigLoader;
+
+impl AssetLoader for WorldGenConfigLoader {
+    type Asset = WorldGenConfig;
+    type Settings = ();
+    type Error = WorldGenConfigError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        WorldGenConfig::from_ron(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["worldgen.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_terrain_fields_regenerate() {
+        let config = WorldGenConfig::default();
+        let tuned = WorldGenConfig { day_length: 60.0, wind_strength: 9.0, snow_max_flakes: 100, render_distance: 4, ..config.clone() };
+        assert!(!config.terrain_differs(&tuned));
+
+        let hillier = WorldGenConfig { noise_amp: 20.0, ..config.clone() };
+        assert!(config.terrain_differs(&hillier) && !config.grid_differs(&hillier));
+
+        let finer = WorldGenConfig { vertex_spacing: 2.0, ..config.clone() };
+        assert!(config.terrain_differs(&finer) && config.grid_differs(&finer));
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use bevy::prelude::*;
+
+use crate::chunks::lod_step;
+use crate::config::WorldGenConfig;
+use crate::world_gen::{ChunkCoord, LOD_RINGS};
+
+// terrain deformation: a height offset per terrain vertex (full resolution grid) on top of the noise height.
+// a chunk owns the vertices 0..chunk_size on both axes, chunks without edits aren't stored at all
+
+/// What pressed into the snow, decides size and depth of the dent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stamp {
+    Footprint,
+    SledTrack,
+    Crater,
+}
+
+impl Stamp {
+    // (radius, depth) in world units
+    fn size(self) -> (f32, f32) {
+        match self {
+            Stamp::Footprint => (0.5, 0.12),
+            Stamp::SledTrack => (0.9, 0.25),
+            Stamp::Crater => (0.8, 0.35),
+        }
+    }
+}
+
+/// Height offsets of all edited chunks. Cloning is cheap, the chunk tasks get a copy of the chunks around them.
+#[derive(Resource, Clone, Default)]
+pub struct TerrainEdits {
+    pub chunks: HashMap<ChunkCoord, Arc<Vec<f32>>>,
+    pub dirty: HashSet<ChunkCoord>, // chunks whose mesh has to be rebuilt
+}
+
+impl TerrainEdits {
+    // offset of one vertex of the global vertex grid
+    fn vertex_offset(&self, gx: i64, gz: i64, config: &WorldGenConfig) -> f32 {
+        let (coord, index) = vertex_slot(gx, gz, config);
+        self.chunks.get(&coord).map_or(0.0, |offsets| offsets[index])
+    }
+
+    /// Offset at any world position, bilinear between the vertices around it.
+    pub fn offset_at(&self, world_x: f64, world_z: f64, config: &WorldGenConfig) -> f32 {
+        if self.chunks.is_empty() {
+            return 0.0;
+        }
+        let fx = world_x / config.vertex_spacing as f64;
+        let fz = world_z / config.vertex_spacing as f64;
+        let (gx, gz) = (fx.floor() as i64, fz.floor() as i64);
+        let (tx, tz) = ((fx - fx.floor()) as f32, (fz - fz.floor()) as f32);
+
+        let a = self.vertex_offset(gx, gz, config);
+        let b = self.vertex_offset(gx + 1, gz, config);
+        let c = self.vertex_offset(gx, gz + 1, config);
+        let d = self.vertex_offset(gx + 1, gz + 1, config);
+        let top = a + (b - a) * tx;
+        let bottom = c + (d - c) * tx;
+        top + (bottom - top) * tz
+    }
+
+    /// Presses a dent into the snow. Pressing again at the same spot doesn't dig any deeper.
+    /// A dent smaller than the vertex spacing is spread over the vertices around it, bilinear.
+    pub fn stamp(&mut self, pos: Vec3, stamp: Stamp, config: &WorldGenConfig) {
+        let (radius, depth) = stamp.size();
+        let spacing = config.vertex_spacing;
+        let reach = radius.max(spacing); // the corners of the cell the dent is in
+
+        let min_x = ((pos.x - reach) / spacing).ceil() as i64;
+        let max_x = ((pos.x + reach) / spacing).floor() as i64;
+        let min_z = ((pos.z - reach) / spacing).ceil() as i64;
+        let max_z = ((pos.z + reach) / spacing).floor() as i64;
+
+        for gz in min_z..=max_z {
+            for gx in min_x..=max_x {
+                let d = Vec2::new(gx as f32 * spacing - pos.x, gz as f32 * spacing - pos.z);
+                let falloff = (1.0 - (d.length() / radius).powi(2)).max(0.0);
+                let share = (1.0 - d.x.abs() / spacing).max(0.0) * (1.0 - d.y.abs() / spacing).max(0.0);
+                let weight = falloff.max(share);
+                if weight <= 0.0 {
+                    continue;
+                }
+                let target = -depth * weight;
+
+                let (coord, index) = vertex_slot(gx, gz, config);
+                let size = config.chunk_size * config.chunk_size;
+                let offsets = self.chunks.entry(coord).or_insert_with(|| Arc::new(vec![0.0; size]));
+                if offsets[index] <= target {
+                    continue;
+                }
+                Arc::make_mut(offsets)[index] = target;
+
+                let size = config.chunk_size as i64;
+                for cz in chunks_reading(gz, size) {
+                    for cx in chunks_reading(gx, size) {
+                        self.dirty.insert(ChunkCoord { x: cx, z: cz });
+                    }
+                }
+            }
+        }
+    }
+
+    // only the edits a chunk mesh can see, that's all a chunk task needs
+    pub fn around(&self, coord: ChunkCoord) -> TerrainEdits {
+        let chunks = (-1..=1)
+            .flat_map(|dx| (-1..=1).map(move |dz| ChunkCoord { x: coord.x + dx, z: coord.z + dz }))
+            .filter_map(|c| self.chunks.get(&c).map(|offsets| (c, offsets.clone())))
+            .collect();
+        TerrainEdits { chunks, dirty: HashSet::new() }
+    }
+}
+
+// chunks on one axis whose mesh has this vertex: its own chunk, the one that has it as the far edge
+// and the ones that have it in their border for the normals. The border is one lod step wide,
+// so this goes by the coarsest lod, whichever lod the neighbours are at right now
+fn chunks_reading(g: i64, size: i64) -> std::ops::RangeInclusive<i32> {
+    let border = lod_step(LOD_RINGS.len() as u32) as i64;
+    (g - border - 1).div_euclid(size) as i32..=(g + border).div_euclid(size) as i32
+}
+
+// chunk and index in its offsets for a vertex of the global grid
+fn vertex_slot(gx: i64, gz: i64, config: &WorldGenConfig) -> (ChunkCoord, usize) {
+    let size = config.chunk_size as i64;
+    let coord = ChunkCoord { x: gx.div_euclid(size) as i32, z: gz.div_euclid(size) as i32 };
+    let index = gz.rem_euclid(size) * size + gx.rem_euclid(size);
+    (coord, index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_dents_stay_small() {
+        let config = WorldGenConfig::default();
+        let spacing = config.vertex_spacing;
+        let mut edits = TerrainEdits::default();
+
+        // right on a vertex in the middle of chunk 0: full depth there, nothing one vertex over
+        let middle = (config.chunk_size / 2) as f32 * spacing;
+        edits.stamp(Vec3::new(middle, 0.0, middle), Stamp::Footprint, &config);
+        assert!((edits.offset_at(middle as f64, middle as f64, &config) + 0.12).abs() < 1e-5);
+        assert_eq!(edits.offset_at((middle + spacing) as f64, middle as f64, &config), 0.0);
+        assert_eq!(edits.dirty, HashSet::from([ChunkCoord { x: 0, z: 0 }]));
+
+        // between vertices the corners share it
+        let mut edits = TerrainEdits::default();
+        edits.stamp(Vec3::new(middle + spacing * 0.5, 0.0, middle), Stamp::Footprint, &config);
+        assert!((edits.offset_at(middle as f64, middle as f64, &config) + 0.06).abs() < 1e-5);
+
+        // on the chunk corner all four chunks have the vertex
+        let mut edits = TerrainEdits::default();
+        edits.stamp(Vec3::ZERO, Stamp::Footprint, &config);
+        assert_eq!(edits.dirty.len(), 4);
+
+        // a few vertices in, the neighbour still has it in its border at a coarse lod
+        let mut edits = TerrainEdits::default();
+        edits.stamp(Vec3::new(4.0 * spacing, 0.0, middle), Stamp::Footprint, &config);
+        assert_eq!(edits.dirty, HashSet::from([ChunkCoord { x: -1, z: 0 }, ChunkCoord { x: 0, z: 0 }]));
+    }
+}
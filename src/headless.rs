@@ -0,0 +1,24 @@
+use bevy::prelude::*;
+use bevy::input::InputPlugin;
+use bevy::log::LogPlugin;
+use bevy::state::app::StatesPlugin;
+use bevy::scene::Scene;
+
+#[cfg(feature = "props")]
+use crate::props::PropModels;
+
+// what MinimalPlugins is missing for the game plugins to run without a window or gpu.
+// chunks still get generated (without prop meshes), the player, snowballs and saves all work
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((LogPlugin::default(), AssetPlugin::default(), TransformPlugin, InputPlugin, StatesPlugin))
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .init_asset::<Scene>();
+
+        #[cfg(feature = "props")]
+        app.insert_resource(PropModels::default());
+    }
+}
@@ -1,9 +1,12 @@
 use bevy::prelude::*;
-use noise::NoiseFn;
 
-use crate::chunks::*;
-use crate::world_gen::*;
+use crate::biome::{Biome, BiomeMap};
 use crate::noise::NoiseGenerators;
+use crate::config::WorldGenConfig;
+use crate::player::FlyCamera;
+use crate::snowball::{SnowballScore, SnowballSettings, SnowballThrower};
+use crate::weather::Weather;
+use crate::wind::Wind;
 
 // Component for HUD Text
 #[derive(Component)]
@@ -58,43 +61,53 @@ fn update_fps(time: Res<Time>, mut fps: ResMut<FpsCounter>) {
     }
 }
 
-// --- SIMPLE biome detection (same rules as terrain) ---
-pub fn detect_biome(x: f32, z: f32, noise: &NoiseGenerators) -> Biome {
-    let bx: f64 = x as f64 * BIOME_FREQ;
-    let bz: f64 = z as f64 * BIOME_FREQ;
-
-    let biome_val: f32 = noise.biome.get([bx, bz]) as f32;
-
-    if biome_val < -0.2 {
-        return Biome::Plains
-    } else{
-        return Biome::Forest
-    };
+// biome detection, same BiomeMap as the terrain
+pub fn detect_biome(x: f32, z: f32, noise: &NoiseGenerators, config: &WorldGenConfig) -> Biome {
+    BiomeMap::new(noise, config).biome_at(x as f64, z as f64)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn update_hud(
     fps: Res<FpsCounter>,
-    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    player_query: Query<&GlobalTransform, With<FlyCamera>>,
     noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
+    score: Res<SnowballScore>,
+    thrower: Res<SnowballThrower>,
+    throw_settings: Res<SnowballSettings>,
+    wind: Res<Wind>,
+    weather: Res<Weather>,
     mut query: Query<&mut Text, With<HudText>>,
 ) {
     let Ok(mut text) = query.single_mut() else { return };
-    let Ok(transform) = camera_query.single() else { return };
+    let Ok(transform) = player_query.single() else { return };
 
     let pos = transform.translation();
 
     // Convert world → chunk coordinates
-    let cx = (pos.x / (CHUNK_SIZE as f32 * VERTEX_SPACING)).floor() as i32;
-    let cz = (pos.z / (CHUNK_SIZE as f32 * VERTEX_SPACING)).floor() as i32;
+    let chunk = config.chunk_coord(pos);
+
+    let biome = detect_biome(pos.x, pos.z, &noise, &config);
+
+    let snowballs = match throw_settings.max_supply {
+        Some(max) => format!("{}/{max}", thrower.supply),
+        None => "endless".to_string(),
+    };
 
-    let biome = detect_biome(pos.x, pos.z, &noise);
+    // wind where the player stands, gusts included
+    let wind_speed = wind.at(pos).length();
+    let gusty = if wind.gust > 0.5 { ", gusty" } else { "" };
 
     // Update HUD text
     **text = format!(
-        "FPS: {:.0}\nPos: {:.1} {:.1} {:.1}\nChunk: {} {}\nBiome: {:?}",
+        "FPS: {:.0}\nPos: {:.1} {:.1} {:.1}\nChunk: {} {}\nBiome: {:?}\nScore: {}\nSnowballs: {}\nWind: {:.1} m/s from {}{}\nWeather: {:?}",
         fps.fps,
         pos.x, pos.y, pos.z,
-        cx, cz,
-        biome
+        chunk.x, chunk.z,
+        biome,
+        score.points,
+        snowballs,
+        wind_speed, wind.from_compass(), gusty,
+        weather.state
     );
 }
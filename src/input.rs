@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy::asset::{io::Reader, AssetLoader, LoadContext};
+use bevy::input::InputSystem;
+use bevy::input::mouse::AccumulatedMouseMotion;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::asset_paths::AssetPaths;
+
+// input actions: gameplay code asks "is Jump pressed", not "is Space pressed".
+// bindings come from assets/input.bindings.ron (hot reloaded) and can be changed while playing
+
+pub const INPUT_BINDINGS_PATH: &str = "input.bindings.ron";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Jump, // also up while flying
+    Descend, // down while flying
+    Sprint,
+    ToggleFly,
+    Throw, // hold to charge
+    Scoop, // hold to pick up snow for more snowballs
+    ToggleSled,
+    ToggleWireframe,
+    CycleCamera, // first person, third person, chase
+    ForceWeather, // debug: steps through the weather states
+    Save,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 16] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Descend,
+        Action::Sprint,
+        Action::ToggleFly,
+        Action::Throw,
+        Action::Scoop,
+        Action::ToggleSled,
+        Action::ToggleWireframe,
+        Action::CycleCamera,
+        Action::ForceWeather,
+        Action::Save,
+        Action::Quit,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+impl Binding {
+    fn same_device(&self, other: &Binding) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// Which inputs trigger which action, plus look sensitivity. Movement and look also read the gamepad sticks.
+#[derive(Asset, TypePath, Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputBindings {
+    pub bindings: HashMap<Action, Vec<Binding>>,
+    pub mouse_sensitivity: f32, // radians per pixel
+    pub stick_look_speed: f32, // radians per second at full tilt
+    pub stick_deadzone: f32,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use Binding::*;
+        let bindings = HashMap::from([
+            (Action::MoveForward, vec![Key(KeyCode::KeyW)]),
+            (Action::MoveBack, vec![Key(KeyCode::KeyS)]),
+            (Action::MoveLeft, vec![Key(KeyCode::KeyA)]),
+            (Action::MoveRight, vec![Key(KeyCode::KeyD)]),
+            (Action::Jump, vec![Key(KeyCode::Space), Gamepad(GamepadButton::South)]),
+            (Action::Descend, vec![Key(KeyCode::ShiftLeft), Gamepad(GamepadButton::East)]),
+            (Action::Sprint, vec![Key(KeyCode::KeyQ), Gamepad(GamepadButton::LeftThumb)]),
+            (Action::ToggleFly, vec![Key(KeyCode::KeyF), Gamepad(GamepadButton::North)]),
+            (Action::Throw, vec![Mouse(MouseButton::Left), Gamepad(GamepadButton::RightTrigger2)]),
+            (Action::Scoop, vec![Key(KeyCode::KeyR), Gamepad(GamepadButton::LeftTrigger2)]),
+            (Action::ToggleSled, vec![Mouse(MouseButton::Right), Gamepad(GamepadButton::West)]),
+            (Action::ToggleWireframe, vec![Key(KeyCode::F3)]),
+            (Action::CycleCamera, vec![Key(KeyCode::KeyV), Gamepad(GamepadButton::Select)]),
+            (Action::ForceWeather, vec![Key(KeyCode::F6)]),
+            (Action::Save, vec![Key(KeyCode::F5)]),
+            (Action::Quit, vec![Key(KeyCode::Escape)]),
+        ]);
+        Self {
+            bindings,
+            mouse_sensitivity: 0.002,
+            stick_look_speed: 3.0,
+            stick_deadzone: 0.15,
+        }
+    }
+}
+
+impl InputBindings {
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Replaces the binding of the same device, so rebinding a key keeps the gamepad button.
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        let list = self.bindings.entry(action).or_default();
+        list.retain(|b| !b.same_device(&binding));
+        list.push(binding);
+    }
+
+    pub fn unbind(&mut self, action: Action, binding: Binding) {
+        if let Some(list) = self.bindings.get_mut(&action) {
+            list.retain(|b| *b != binding);
+        }
+    }
+}
+
+/// What the player wants to do this frame, built from the bindings in PreUpdate.
+#[derive(Resource, Default, Debug)]
+pub struct ActionState {
+    pressed: Vec<Action>,
+    just_pressed: Vec<Action>,
+    just_released: Vec<Action>,
+    pub movement: Vec2, // x = right, y = forward, length at most 1
+    pub look: Vec2, // radians this frame, x = yaw, y = pitch
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.just_released.contains(&action)
+    }
+}
+
+/// Send this to rebind an action to the next key, mouse button or gamepad button that gets pressed.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct RebindAction(pub Action);
+
+#[derive(Resource)]
+pub struct InputBindingsHandle(pub Handle<InputBindings>);
+
+pub struct InputActionsPlugin;
+
+impl Plugin for InputActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputBindings>()
+            .init_resource::<ActionState>()
+            .init_resource::<AssetPaths>()
+            .init_asset::<InputBindings>()
+            .init_asset_loader::<InputBindingsLoader>()
+            .add_event::<RebindAction>()
+            .add_systems(Startup, load_bindings)
+            .add_systems(PreUpdate, (apply_bindings, capture_rebind, update_action_state).chain().after(InputSystem));
+    }
+}
+
+fn load_bindings(mut commands: Commands, asset_server: Res<AssetServer>, paths: Res<AssetPaths>) {
+    commands.insert_resource(InputBindingsHandle(asset_server.load(&paths.input_bindings)));
+}
+
+// same as the world gen config: copy the asset into the resource when it's loaded or changed
+fn apply_bindings(
+    mut events: EventReader<AssetEvent<InputBindings>>,
+    handle: Option<Res<InputBindingsHandle>>,
+    assets: Res<Assets<InputBindings>>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    let Some(handle) = handle else { return };
+
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) && !event.is_modified(&handle.0) {
+            continue;
+        }
+        if let Some(new_bindings) = assets.get(&handle.0) {
+            *bindings = new_bindings.clone();
+            info!("input bindings loaded");
+        }
+    }
+}
+
+// waits for the next button after a RebindAction
+fn capture_rebind(
+    mut requests: EventReader<RebindAction>,
+    mut waiting: Local<Option<Action>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    if let Some(RebindAction(action)) = requests.read().last() {
+        *waiting = Some(*action);
+        info!("press a key or button for {action:?}");
+        return;
+    }
+    let Some(action) = *waiting else { return };
+
+    let pressed = keys.get_just_pressed().next().map(|k| Binding::Key(*k))
+        .or_else(|| mouse.get_just_pressed().next().map(|b| Binding::Mouse(*b)))
+        .or_else(|| gamepads.iter().find_map(|g| g.get_just_pressed().next()).map(|b| Binding::Gamepad(*b)));
+
+    if let Some(binding) = pressed {
+        bindings.rebind(action, binding);
+        *waiting = None;
+        info!("{action:?} is now {binding:?}");
+    }
+}
+
+// radial deadzone, rescaled so the stick still reaches 1
+fn deadzone(stick: Vec2, zone: f32) -> Vec2 {
+    let len = stick.length();
+    if len <= zone {
+        return Vec2::ZERO;
+    }
+    stick * ((len - zone) / (1.0 - zone) / len).min(1.0 / len)
+}
+
+fn update_action_state(
+    bindings: Res<InputBindings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    gamepads: Query<&Gamepad>,
+    time: Res<Time>,
+    mut state: ResMut<ActionState>,
+) {
+    #[derive(Clone, Copy)]
+    enum Edge { Held, Pressed, Released }
+    let is = |binding: &Binding, edge: Edge| match (binding, edge) {
+        (Binding::Key(key), Edge::Held) => keys.pressed(*key),
+        (Binding::Key(key), Edge::Pressed) => keys.just_pressed(*key),
+        (Binding::Key(key), Edge::Released) => keys.just_released(*key),
+        (Binding::Mouse(button), Edge::Held) => mouse.pressed(*button),
+        (Binding::Mouse(button), Edge::Pressed) => mouse.just_pressed(*button),
+        (Binding::Mouse(button), Edge::Released) => mouse.just_released(*button),
+        (Binding::Gamepad(button), Edge::Held) => gamepads.iter().any(|g| g.pressed(*button)),
+        (Binding::Gamepad(button), Edge::Pressed) => gamepads.iter().any(|g| g.just_pressed(*button)),
+        (Binding::Gamepad(button), Edge::Released) => gamepads.iter().any(|g| g.just_released(*button)),
+    };
+    let actions = |edge: Edge| Action::ALL.into_iter().filter(|a| bindings.get(*a).iter().any(|b| is(b, edge))).collect();
+
+    state.pressed = actions(Edge::Held);
+    state.just_pressed = actions(Edge::Pressed);
+    state.just_released = actions(Edge::Released);
+
+    // movement: digital actions plus the left stick
+    let mut movement = Vec2::ZERO;
+    if state.pressed(Action::MoveForward) { movement.y += 1.0; }
+    if state.pressed(Action::MoveBack) { movement.y -= 1.0; }
+    if state.pressed(Action::MoveRight) { movement.x += 1.0; }
+    if state.pressed(Action::MoveLeft) { movement.x -= 1.0; }
+    for gamepad in &gamepads {
+        movement += deadzone(gamepad.left_stick(), bindings.stick_deadzone);
+    }
+    state.movement = movement.clamp_length_max(1.0);
+
+    // look: mouse pixels plus the right stick (stick up = look up)
+    let mut look = -mouse_motion.delta * bindings.mouse_sensitivity;
+    for gamepad in &gamepads {
+        let stick = deadzone(gamepad.right_stick(), bindings.stick_deadzone);
+        look += Vec2::new(-stick.x, stick.y) * bindings.stick_look_speed * time.delta_secs();
+    }
+    state.look = look;
+}
+
+#[derive(Debug, Error)]
+pub enum InputBindingsError {
+    #[error("could not read input bindings: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse input bindings: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+#[derive(Default)]
+pub struct InputBindingsLoader;
+
+impl AssetLoader for InputBindingsLoader {
+    type Asset = InputBindings;
+    type Settings = ();
+    type Error = InputBindingsError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bindings.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_bindings_cover_every_action() {
+        let bindings: InputBindings = ron::de::from_str(include_str!("../assets/input.bindings.ron")).unwrap();
+        for action in Action::ALL {
+            assert!(!bindings.get(action).is_empty(), "{action:?} has no binding");
+        }
+    }
+
+    #[test]
+    fn rebinding_keeps_the_other_devices() {
+        let mut bindings = InputBindings::default();
+        bindings.rebind(Action::Jump, Binding::Key(KeyCode::KeyJ));
+
+        assert_eq!(
+            bindings.get(Action::Jump),
+            &[Binding::Gamepad(GamepadButton::South), Binding::Key(KeyCode::KeyJ)]
+        );
+    }
+}
@@ -0,0 +1,29 @@
+pub mod player;
+pub mod world;
+#[cfg(feature = "snow")]
+pub mod snowflake;
+pub mod world_gen;
+pub mod chunks;
+#[cfg(feature = "hud")]
+pub mod hud;
+pub mod noise;
+pub mod config;
+pub mod biome;
+#[cfg(feature = "props")]
+pub mod props;
+pub mod collision;
+pub mod deform;
+pub mod save;
+pub mod cli;
+pub mod headless;
+pub mod asset_paths;
+pub mod input;
+pub mod camera;
+pub mod snowball;
+pub mod wind;
+pub mod weather;
+pub mod sky;
+pub mod night_sky;
+pub mod plugins;
+
+pub use plugins::WinterPlugins;
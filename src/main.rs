@@ -1,32 +1,63 @@
+use std::process::ExitCode;
+use std::time::Duration;
+
 use bevy::prelude::*;
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::window::{MonitorSelection, WindowMode};
+
+use bevy_winter::WinterPlugins;
+use bevy_winter::cli::{CliError, LaunchOptions, USAGE};
+use bevy_winter::headless::HeadlessPlugin;
+use bevy_winter::input::{Action, ActionState};
 
-use crate::player::PlayerPlugin;
-use crate::world::WorldPlugin;
-use crate::snowflake::SnowflakePlugin;
-use crate::world_gen::WorldGenPlugin;
-use crate::hud::HudPlugin;
+fn main() -> ExitCode {
+    let options = match LaunchOptions::from_env() {
+        Ok(options) => options,
+        Err(CliError::Help) => {
+            eprintln!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
 
-mod player;
-mod world;
-mod snowflake;
-mod world_gen;
-mod chunks;
-mod hud;
-mod noise;
+    let mut app = App::new();
+    let winter = WinterPlugins::from_options(&options);
+
+    if options.headless {
+        // 60 ticks per second instead of spinning a core
+        app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / 60.0))),
+            HeadlessPlugin,
+            winter.snow(false).hud(false).wireframe(false),
+        ));
+    } else {
+        let mode = if options.fullscreen {
+            WindowMode::BorderlessFullscreen(MonitorSelection::Primary)
+        } else {
+            WindowMode::Windowed
+        };
+        app.add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window { mode, ..default() }),
+                ..default()
+            }),
+            winter,
+        ))
+        .add_systems(Update, exit_on_quit);
+    }
 
-fn main() {
-    App::new()
-        .insert_resource(noise::NoiseGenerators::new(67))
-        .add_plugins((DefaultPlugins, PlayerPlugin, WorldPlugin, SnowflakePlugin, WorldGenPlugin, HudPlugin))
-        .add_systems(Update, exit_on_esc)
-        .run();
+    app.insert_resource(options).run();
+    ExitCode::SUCCESS
 }
 
-fn exit_on_esc(
-    keys: Res<ButtonInput<KeyCode>>,
+fn exit_on_quit(
+    actions: Res<ActionState>,
     mut exit: EventWriter<AppExit>,
 ) {
-    if keys.just_pressed(KeyCode::Escape) {
+    if actions.just_pressed(Action::Quit) {
         exit.write(AppExit::Success);
     }
 }
@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+use bevy::asset::RenderAssetUsages;
+use bevy::pbr::NotShadowCaster;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::view::NoFrustumCulling;
+use bevy::transform::TransformSystem;
+use noise::{NoiseFn, Perlin};
+use std::f32::consts::TAU;
+
+use crate::camera::follow_player;
+use crate::noise::NoiseGenerators;
+use crate::sky::SkyLight;
+use crate::weather::Weather;
+
+// stars and aurora curtains on a dome that moves with the camera, so they never get any closer.
+// where the stars are and how the curtains wave comes from the world seed.
+// both fade in at night under a clear sky, the fog doesn't touch them
+
+const DOME_RADIUS: f32 = 900.0; // inside the far plane
+const STAR_SALT: u64 = 0x57A2;
+const CURTAINS: usize = 3;
+const COLUMNS: usize = 64; // per curtain
+const ROWS: usize = 6; // vertices from the bottom edge of a curtain to the top
+
+/// How the night sky looks. Changing the star count places the stars again.
+#[derive(Resource, Clone, Debug)]
+pub struct NightSkySettings {
+    pub star_count: usize,
+    pub star_brightness: f32,
+    pub aurora_intensity: f32, // 0 turns it off
+    pub aurora_speed: f32,
+    pub aurora_bands: Vec<Vec3>, // colours from the bottom of a curtain to the top, srgb
+}
+
+impl Default for NightSkySettings {
+    fn default() -> Self {
+        Self {
+            star_count: 1500,
+            star_brightness: 4.0,
+            aurora_intensity: 3.0,
+            aurora_speed: 1.0,
+            aurora_bands: vec![
+                Vec3::new(0.2, 1.0, 0.5), // the green bottom edge
+                Vec3::new(0.1, 0.8, 0.7),
+                Vec3::new(0.6, 0.3, 0.9), // violet up high
+            ],
+        }
+    }
+}
+
+#[derive(Component)]
+struct NightSky;
+
+#[derive(Component)]
+struct Stars(Handle<Mesh>);
+
+#[derive(Component)]
+struct Aurora(Handle<Mesh>);
+
+pub struct NightSkyPlugin;
+
+impl Plugin for NightSkyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NightSkySettings>()
+            .add_systems(Startup, setup_night_sky)
+            .add_systems(Update, (build_stars, fade_night_sky, update_aurora).chain())
+            .add_systems(PostUpdate, follow_camera.after(follow_player).before(TransformSystem::TransformPropagate));
+    }
+}
+
+// the meshes come later, the stars when the seed is known and the aurora every frame
+fn setup_night_sky(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // additive and unlit, black adds nothing so base_color fades them in and out
+    let material = StandardMaterial {
+        base_color: Color::BLACK,
+        alpha_mode: AlphaMode::Add,
+        unlit: true,
+        fog_enabled: false,
+        double_sided: true,
+        cull_mode: None,
+        ..default()
+    };
+    let stars = meshes.reserve_handle();
+    let aurora = meshes.reserve_handle();
+
+    commands.spawn((NightSky, Transform::default(), Visibility::default()))
+        .with_children(|sky| {
+            sky.spawn((
+                Mesh3d(stars.clone()),
+                MeshMaterial3d(materials.add(material.clone())),
+                Stars(stars),
+                Transform::default(),
+                Visibility::Hidden,
+                NotShadowCaster,
+            ));
+            sky.spawn((
+                Mesh3d(aurora.clone()),
+                MeshMaterial3d(materials.add(material)),
+                Aurora(aurora),
+                Transform::default(),
+                Visibility::Hidden,
+                NotShadowCaster,
+                // the curtains move every frame, the first bounding box would be wrong
+                NoFrustumCulling,
+            ));
+        });
+}
+
+/// Quads facing the middle of the dome, the same seed gives the same sky.
+fn star_mesh(noise: &NoiseGenerators, count: usize) -> Mesh {
+    let mut positions = Vec::with_capacity(count * 4);
+    let mut colors = Vec::with_capacity(count * 4);
+    let mut indices = Vec::with_capacity(count * 6);
+    for i in 0..count {
+        let hash = |salt: u64| noise.hash01(i as i64, 0, STAR_SALT + salt);
+
+        // evenly spread over the upper half, a bit below the horizon too
+        let y = hash(0) * 1.1 - 0.1;
+        let angle = hash(1) * TAU;
+        let r = (1.0 - y * y).sqrt();
+        let dir = Vec3::new(r * angle.cos(), y, r * angle.sin());
+
+        // most stars are faint, a few are big and bright
+        let brightness = hash(2).powi(4) * 0.9 + 0.1;
+        let size = 1.0 + brightness * 2.5;
+        let (a, b) = dir.any_orthonormal_pair();
+        let (a, b, center) = (a * size, b * size, dir * DOME_RADIUS);
+        positions.extend([center - a - b, center + a - b, center + a + b, center - a + b].map(|p| p.to_array()));
+
+        let tint = Vec3::new(0.8, 0.9, 1.0).lerp(Vec3::new(1.0, 0.9, 0.75), hash(3));
+        colors.extend([(tint * brightness).extend(1.0).to_array(); 4]);
+
+        let v = i as u32 * 4;
+        indices.extend([v, v + 1, v + 2, v, v + 2, v + 3]);
+    }
+
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0f32, 1.0, 0.0]; positions.len()])
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+// colour at height v (0..1) of a curtain, linear
+fn band_color(bands: &[Vec3], v: f32) -> Vec3 {
+    let linear = |c: Vec3| Vec3::from_slice(&Color::srgb(c.x, c.y, c.z).to_linear().to_f32_array()[..3]);
+    match bands {
+        [] => Vec3::ZERO,
+        [only] => linear(*only),
+        _ => {
+            let x = v.clamp(0.0, 1.0) * (bands.len() - 1) as f32;
+            let i = (x.floor() as usize).min(bands.len() - 2);
+            linear(bands[i]).lerp(linear(bands[i + 1]), x - i as f32)
+        }
+    }
+}
+
+/// A few curtains hanging in the northern sky, waving with `t`.
+fn aurora_mesh(perlin: &Perlin, bands: &[Vec3], t: f64) -> Mesh {
+    let mut positions = Vec::with_capacity(CURTAINS * COLUMNS * ROWS);
+    let mut colors = Vec::with_capacity(CURTAINS * COLUMNS * ROWS);
+    let mut indices = Vec::with_capacity(CURTAINS * (COLUMNS - 1) * (ROWS - 1) * 6);
+
+    for curtain in 0..CURTAINS {
+        let z = curtain as f64 * 17.0;
+        let center = perlin.get([0.5, z, 3.3]) as f32 * 0.8; // radians off north
+        let elevation = 0.3 + curtain as f32 * 0.08;
+
+        for column in 0..COLUMNS {
+            let u = column as f32 / (COLUMNS - 1) as f32;
+            let along = u as f64 * 3.0;
+            let azimuth = center + (u - 0.5) * 1.6 + perlin.get([along, z, t * 0.05]) as f32 * 0.25;
+            let bottom = elevation + perlin.get([along * 0.7, z + 5.0, t * 0.03]) as f32 * 0.08;
+            let height = 0.25 + perlin.get([along * 1.3, z + 9.0, t * 0.04]) as f32 * 0.1;
+            // rays: brighter and darker stripes drifting along the curtain
+            let rays = (perlin.get([along * 8.0, z + 13.0, t * 0.2]) as f32 * 0.5 + 0.5).powi(2);
+            let ends = (u * (1.0 - u) * 8.0).min(1.0);
+
+            for row in 0..ROWS {
+                let v = row as f32 / (ROWS - 1) as f32;
+                let el = bottom + height * v;
+                let dir = Vec3::new(azimuth.sin() * el.cos(), el.sin(), -azimuth.cos() * el.cos());
+                positions.push((dir * DOME_RADIUS * 0.95).to_array());
+
+                // sharp at the bottom, fading out at the top
+                let fade = (v * 6.0).min(1.0) * (1.0 - v).powf(1.5);
+                colors.push((band_color(bands, v) * rays * ends * fade).extend(1.0).to_array());
+            }
+        }
+
+        let first = (curtain * COLUMNS * ROWS) as u32;
+        for column in 0..COLUMNS - 1 {
+            for row in 0..ROWS - 1 {
+                let a = first + (column * ROWS + row) as u32;
+                let b = a + ROWS as u32;
+                indices.extend([a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+    }
+
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0f32, 0.0, 1.0]; positions.len()])
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+// a loaded save changes the seed
+fn build_stars(
+    noise: Res<NoiseGenerators>,
+    settings: Res<NightSkySettings>,
+    stars_query: Query<&Stars>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !noise.is_changed() && !settings.is_changed() {
+        return;
+    }
+    for stars in &stars_query {
+        meshes.insert(&stars.0, star_mesh(&noise, settings.star_count));
+    }
+}
+
+// the stars come through a thin flurry, heavy snow hides them
+#[allow(clippy::type_complexity)]
+fn fade_night_sky(
+    sky_light: Res<SkyLight>,
+    weather: Res<Weather>,
+    settings: Res<NightSkySettings>,
+    mut parts: Query<(&MeshMaterial3d<StandardMaterial>, &mut Visibility, Has<Aurora>), Or<(With<Stars>, With<Aurora>)>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let clear = ((weather.params.sun - 0.4) / 0.6).clamp(0.0, 1.0);
+    let shown = sky_light.night * clear;
+
+    for (material, mut visibility, is_aurora) in &mut parts {
+        let brightness = shown * if is_aurora { settings.aurora_intensity } else { settings.star_brightness };
+        visibility.set_if_neq(if brightness > 0.001 { Visibility::Inherited } else { Visibility::Hidden });
+
+        // every change uploads the material again
+        let current = materials.get(&material.0).map(|m| m.base_color.to_linear().red);
+        if current.is_some_and(|c| (c - brightness).abs() > 0.01)
+            && let Some(m) = materials.get_mut(&material.0)
+        {
+            m.base_color = Color::linear_rgb(brightness, brightness, brightness);
+        }
+    }
+}
+
+fn update_aurora(
+    time: Res<Time>,
+    noise: Res<NoiseGenerators>,
+    settings: Res<NightSkySettings>,
+    aurora_query: Query<(&Aurora, &Visibility)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let Ok((aurora, visibility)) = aurora_query.single() else { return };
+    if visibility == Visibility::Hidden {
+        return;
+    }
+    let t = time.elapsed_secs_f64() * settings.aurora_speed as f64;
+    meshes.insert(&aurora.0, aurora_mesh(&noise.aurora, &settings.aurora_bands, t));
+}
+
+// after the camera moved, so the dome doesn't lag behind
+fn follow_camera(
+    camera_query: Query<&Transform, (With<Camera3d>, Without<NightSky>)>,
+    mut sky_query: Query<&mut Transform, With<NightSky>>,
+) {
+    let Ok(camera) = camera_query.single() else { return };
+    for mut sky in &mut sky_query {
+        sky.translation = camera.translation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    fn star_positions(seed: u32) -> Vec<[f32; 3]> {
+        match star_mesh(&NoiseGenerators::new(seed), 50).attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions.clone(),
+            _ => panic!("no positions"),
+        }
+    }
+
+    #[test]
+    fn the_seed_decides_the_sky() {
+        assert_eq!(star_positions(7), star_positions(7));
+        assert_ne!(star_positions(7), star_positions(8));
+
+        let noise = NoiseGenerators::new(7);
+        let bands = NightSkySettings::default().aurora_bands;
+        assert_eq!(aurora_mesh(&noise.aurora, &bands, 2.0).count_vertices(), CURTAINS * COLUMNS * ROWS);
+        assert!(band_color(&bands, 0.0).abs_diff_eq(band_color(&bands[..1], 0.5), 1e-5));
+    }
+}
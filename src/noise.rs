@@ -1,21 +1,50 @@
-use noise::{Perlin, Seedable};
+use noise::{MultiFractal, Perlin, RidgedMulti, Seedable};
 use bevy::prelude::*;
 
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct NoiseGenerators {
+    pub seed: u32,
     pub height: Perlin,
     pub biome: Perlin,
     pub tree: Perlin,
-    pub candy_cane: Perlin
+    pub candy_cane: Perlin,
+    pub climate: Perlin, // second biome axis, warm or cold
+    pub ridge: RidgedMulti, // glacier peaks
+    pub shrub: Perlin,
+    pub wind: Perlin, // sampled over time, not space
+    pub aurora: Perlin,
 }
 
 impl NoiseGenerators {
     pub fn new(seed: u32) -> Self {
         Self {
+            seed,
             height: Perlin::new().set_seed(seed), 
-            biome: Perlin::new().set_seed(seed + 69),
-            tree: Perlin::new().set_seed(seed + 89),
-            candy_cane: Perlin::new().set_seed(seed + 109),
+            biome: Perlin::new().set_seed(seed.wrapping_add(69)),
+            tree: Perlin::new().set_seed(seed.wrapping_add(89)),
+            candy_cane: Perlin::new().set_seed(seed.wrapping_add(109)),
+            climate: Perlin::new().set_seed(seed.wrapping_add(129)),
+            ridge: RidgedMulti::new().set_seed(seed.wrapping_add(149)).set_octaves(4),
+            shrub: Perlin::new().set_seed(seed.wrapping_add(169)),
+            wind: Perlin::new().set_seed(seed.wrapping_add(189)),
+            aurora: Perlin::new().set_seed(seed.wrapping_add(209)),
         }
     }
-}
\ No newline at end of file
+
+    // deterministic "random" number in 0..1 for a grid position, same seed + position + salt = same number.
+    // used for prop rotation/scale so chunks look the same every time they load
+    pub fn hash01(&self, grid_x: i64, grid_z: i64, salt: u64) -> f32 {
+        let mut h = (self.seed as u64) ^ salt.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        h = splitmix64(h ^ grid_x as u64);
+        h = splitmix64(h ^ grid_z as u64);
+        // top 24 bits, exactly representable as f32
+        (h >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
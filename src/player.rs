@@ -1,23 +1,56 @@
 use bevy::prelude::*;
 use bevy::window::{CursorGrabMode, PrimaryWindow};
 use std::f32::consts::FRAC_PI_2;
-use bevy::core_pipeline::bloom::Bloom;
 
 use crate::noise::NoiseGenerators;
-use crate::chunks::get_height;
+use crate::chunks::{get_height, get_surface_height};
 use crate::chunks::get_surface_normal;
-use crate::world_gen::*;
+use crate::config::WorldGenConfig;
+use crate::collision::ColliderGrid;
+use crate::deform::{Stamp, TerrainEdits};
+use crate::asset_paths::AssetPaths;
+use crate::biome::{Biome, BiomeMap};
+use crate::input::{Action, ActionState};
+use crate::wind::Wind;
+
+const PLAYER_RADIUS: f32 = 0.35;
+pub const PLAYER_EYE_HEIGHT: f32 = 1.0; // eyes above the ground
+const GROUND_SNAP: f32 = 0.25;
+const PLAYER_BODY_HEIGHT: f32 = 1.8;
+const SLED_RADIUS: f32 = 0.6;
+const SLED_SEAT_HEIGHT: f32 = 0.3; // where the player sits on the sled
+const SLED_CRASH_SPEED: f32 = 12.0; // faster than this into a tree and you fall off
+const SLED_GRAVITY: f32 = 25.0; // same as falling on foot
+const SLED_TURN_RATE: f32 = 1.8; // radians per second at full speed
+const SLED_GRIP: f32 = 6.0; // how fast sideways sliding dies down on snow
+const SLED_BRAKE: f32 = 0.5; // extra friction with the brake held
+const SLED_DRAG: f32 = 0.004; // quadratic air drag, the wind counts
+const SLED_HOP: f32 = 5.0;
+const SLED_LIFTOFF: f32 = 0.3; // ground this far below the runners = airborne
+const STEP_LENGTH: f32 = 1.2; // distance between footprints
+const SLED_TRACK_STEP: f32 = 0.6;
+
+/// Where the player starts, on top of the terrain.
+#[derive(Resource, Clone, Copy)]
+pub struct PlayerSpawn {
+    pub x: f32,
+    pub z: f32,
+}
+
+impl Default for PlayerSpawn {
+    fn default() -> Self {
+        Self { x: 0.0, z: 5.0 }
+    }
+}
 
+/// The player. The transform is at the eyes and looks where the player looks,
+/// the real camera follows it through the camera rig.
 #[derive(Component)]
 pub struct FlyCamera {
     pub pitch: f32,
     pub yaw: f32,
-    pub sensitivity: f32,
     pub speed: f32,
     pub velocity: Vec3,
-    pub grounded: bool,
-    pub flying: bool,
-    pub sledding: bool,
 }
 
 impl Default for FlyCamera {
@@ -25,41 +58,63 @@ impl Default for FlyCamera {
         Self {
             pitch: 0.0,
             yaw: 0.0,
-            sensitivity: 0.002,
             speed: 10.0,
             velocity: Vec3::ZERO,
-            grounded: true,
-            flying: false,
-            sledding: false
         }
     }
 }
 
+/// What the player is doing right now, every movement system only runs in its own mode.
+///
+/// Walking <-> Airborne (jump, falling off something), Walking <-> Skating (stepping on lake ice),
+/// F toggles Flying from anywhere on foot, right click gets on and off the sled (only from the ground).
+#[derive(States, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PlayerMode {
+    #[default]
+    Walking,
+    Airborne,
+    Flying,
+    Sledding,
+    Skating,
+}
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, (spawn_camera, grab_cursor, load_slead))
-            .add_systems(Update, (camera_movement, camera_look, handle_input, move_snowballs, sledding_system));
+        let on_foot = in_state(PlayerMode::Walking).or(in_state(PlayerMode::Airborne)).or(in_state(PlayerMode::Skating));
+        let on_ground = in_state(PlayerMode::Walking).or(in_state(PlayerMode::Skating));
+
+        app.init_resource::<PlayerSpawn>()
+            .init_resource::<AssetPaths>()
+            .init_state::<PlayerMode>()
+            .add_systems(Startup, (spawn_player, grab_cursor, load_slead))
+            .add_systems(Update, camera_look)
+            .add_systems(Update, (
+                toggle_flying.run_if(on_foot.clone().or(in_state(PlayerMode::Flying))),
+                toggle_sled.run_if(on_ground.clone().or(in_state(PlayerMode::Sledding))),
+                walking_movement.run_if(in_state(PlayerMode::Walking).or(in_state(PlayerMode::Airborne))),
+                skating_movement.run_if(in_state(PlayerMode::Skating)),
+                flying_movement.run_if(in_state(PlayerMode::Flying)),
+                sledding_system.run_if(in_state(PlayerMode::Sledding)),
+            ).chain())
+            .add_systems(OnEnter(PlayerMode::Sledding), spawn_sled)
+            .add_systems(OnExit(PlayerMode::Sledding), despawn_sled);
     }
 }
 
-fn spawn_camera(mut commands: Commands) {
+fn spawn_player(
+    mut commands: Commands,
+    config: Res<WorldGenConfig>,
+    noise: Res<NoiseGenerators>,
+    spawn: Res<PlayerSpawn>,
+) {
+    let ground = get_height(spawn.x as f64, spawn.z as f64, &noise, &config);
+
     commands.spawn((
-        Camera3d::default(),
         FlyCamera::default(),
-        Bloom::NATURAL, 
-        DistanceFog {
-                color: Color::srgb(0.8, 0.9, 1.0),
-                falloff: FogFalloff::Linear {
-                    start: (RENDER_DISTANCE as f32) * CHUNK_SIZE as f32,
-                    end: (RENDER_DISTANCE as f32 + 10.0) * CHUNK_SIZE as f32,
-                },
-                ..default()
-        },
-        Transform::from_xyz(0.0, 1.5, 5.0),
+        Transform::from_xyz(spawn.x, ground + 1.5, spawn.z),
     ));
-    
 }
 
 fn grab_cursor(mut window: Query<&mut Window, With<PrimaryWindow>>) {
@@ -69,209 +124,211 @@ fn grab_cursor(mut window: Query<&mut Window, With<PrimaryWindow>>) {
     }
 }
 
+// mouse and right stick
 fn camera_look(
-    mut motion: EventReader<bevy::input::mouse::MouseMotion>,
+    actions: Res<ActionState>,
     mut query: Query<(&mut Transform, &mut FlyCamera)>,
 ) {
     let Ok((mut transform, mut camera)) = query.single_mut() else { return };
+    if actions.look == Vec2::ZERO {
+        return;
+    }
 
-    for ev in motion.read() {
-        camera.yaw   -= ev.delta.x * camera.sensitivity;
-        camera.pitch -= ev.delta.y * camera.sensitivity;
+    camera.yaw   += actions.look.x;
+    camera.pitch += actions.look.y;
 
-        // Clamp pitch
-        camera.pitch = camera.pitch.clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+    // Clamp pitch
+    camera.pitch = camera.pitch.clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
 
-        // Build two rotations: yaw → world, pitch → local X
-        let yaw_rot   = Quat::from_rotation_y(camera.yaw);
-        let pitch_rot = Quat::from_rotation_x(camera.pitch);
+    // Build two rotations: yaw → world, pitch → local X
+    let yaw_rot   = Quat::from_rotation_y(camera.yaw);
+    let pitch_rot = Quat::from_rotation_x(camera.pitch);
 
-        // Combine them
-        transform.rotation = yaw_rot * pitch_rot;
-    }
+    // Combine them
+    transform.rotation = yaw_rot * pitch_rot;
 }
 
-fn camera_movement(
-    keyboard: Res<ButtonInput<KeyCode>>,
-    time: Res<Time>,
-    noise: Res<NoiseGenerators>,  
-    mut query: Query<(&mut Transform, &mut FlyCamera)>,
-) {
-    let Ok((mut transform, mut camera)) = query.single_mut() else {
-        return;
-    };
-    // check if sledding
-    if camera.sledding {
-        return;
-    }
+// movement input on the ground plane, a half pushed stick walks slower
+fn flat_input(actions: &ActionState, transform: &Transform) -> Vec3 {
+    let forward = transform.forward().with_y(0.0).normalize_or_zero();
+    let right = transform.right().with_y(0.0).normalize_or_zero();
 
-    // toggle flight
-    if keyboard.just_pressed(KeyCode::KeyF) {
-        camera.flying = !camera.flying;
-        camera.velocity = Vec3::ZERO;
-        if camera.flying {
-            camera.grounded = false;
-        }
-    }
-
-    let dt = time.delta_secs();
-    let mut direction = Vec3::ZERO;
+    (forward * actions.movement.y + right * actions.movement.x).clamp_length_max(1.0)
+}
 
-    let forward = *transform.forward();
-    let right = *transform.right();
+// frozen lakes, same threshold as the ice mesh
+fn on_ice(pos: Vec3, noise: &NoiseGenerators, config: &WorldGenConfig) -> bool {
+    BiomeMap::new(noise, config).weights(pos.x as f64, pos.z as f64).get(Biome::FrozenLake) >= 0.5
+}
 
-    if camera.flying {
-        // flying
-        if keyboard.pressed(KeyCode::KeyW) { direction += forward; }
-        if keyboard.pressed(KeyCode::KeyS) { direction -= forward; }
-        if keyboard.pressed(KeyCode::KeyA) { direction -= right; }
-        if keyboard.pressed(KeyCode::KeyD) { direction += right; }
-        
-        if keyboard.pressed(KeyCode::Space) { direction += Vec3::Y; }
-        if keyboard.pressed(KeyCode::ShiftLeft) { direction -= Vec3::Y; }
-
-        if direction.length() > 0.0 {
-            direction = direction.normalize();
-        }
-        
-        transform.translation += direction * camera.speed * 3.0 * dt; // * 3.0 to make flying fastern than walking
-    } else {
-        // walking
-        let forward_flat = Vec3::new(forward.x, 0.0, forward.z).normalize_or_zero();
-        let right_flat = Vec3::new(right.x, 0.0, right.z).normalize_or_zero();
+// gravity, ground contact and prop collision for the modes on foot. Returns the mode the player is in afterwards
+fn fall_and_land(
+    transform: &mut Transform,
+    camera: &mut FlyCamera,
+    dt: f32,
+    noise: &NoiseGenerators,
+    config: &WorldGenConfig,
+    edits: &TerrainEdits,
+    colliders: &ColliderGrid,
+) -> PlayerMode {
+    let gravity = -25.0;
+    camera.velocity.y += gravity * dt;
+    transform.translation.y += camera.velocity.y * dt;
+
+    let terrain_h = get_surface_height(
+        transform.translation.x as f64,
+        transform.translation.z as f64,
+        noise,
+        config,
+        edits,
+    );
+    let ground_y = terrain_h + PLAYER_EYE_HEIGHT;
+
+    // slide along trees and props instead of walking through them
+    let feet = transform.translation - Vec3::Y * PLAYER_EYE_HEIGHT;
+    if let Some(contact) = colliders.resolve(feet, PLAYER_BODY_HEIGHT, PLAYER_RADIUS, config) {
+        transform.translation += contact.push;
+    }
 
-        if keyboard.pressed(KeyCode::KeyW) { direction += forward_flat; }
-        if keyboard.pressed(KeyCode::KeyS) { direction -= forward_flat; }
-        if keyboard.pressed(KeyCode::KeyA) { direction -= right_flat; }
-        if keyboard.pressed(KeyCode::KeyD) { direction += right_flat; }
+    // a bit of snapping so walking downhill doesn't count as falling
+    let falling = camera.velocity.y > 0.0 || transform.translation.y > ground_y + GROUND_SNAP;
+    if falling {
+        return PlayerMode::Airborne;
+    }
 
-        if direction.length() > 0.0 {
-            direction = direction.normalize();
-        }
+    transform.translation.y = ground_y;
+    camera.velocity.y = 0.0;
+    if on_ice(transform.translation, noise, config) {
+        PlayerMode::Skating
+    } else {
+        PlayerMode::Walking
+    }
+}
 
-        if keyboard.pressed(KeyCode::KeyQ) {
-            transform.translation += direction * camera.speed * dt * 2.0;
-        }
-        else {
-            transform.translation += direction * camera.speed * dt
-        }
+// the toggles run earlier in the chain, a mode the player asked for this frame wins over what the movement runs into
+fn switch_mode(next_mode: &mut NextState<PlayerMode>, mode: PlayerMode) {
+    if !matches!(next_mode, NextState::Pending(_)) {
+        next_mode.set(mode);
+    }
+}
 
-        let gravity = -25.0;
+#[allow(clippy::too_many_arguments)]
+fn walking_movement(
+    actions: Res<ActionState>,
+    time: Res<Time>,
+    noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
+    colliders: Res<ColliderGrid>,
+    mode: Res<State<PlayerMode>>,
+    mut next_mode: ResMut<NextState<PlayerMode>>,
+    mut edits: ResMut<TerrainEdits>,
+    mut last_step: Local<Vec3>,
+    mut query: Query<(&mut Transform, &mut FlyCamera)>,
+) {
+    let Ok((mut transform, mut camera)) = query.single_mut() else { return };
+    let dt = time.delta_secs();
 
-        if camera.grounded && keyboard.just_pressed(KeyCode::Space) {
-            camera.velocity.y = 8.0;
-            camera.grounded = false;
-        }
+    let sprint = if actions.pressed(Action::Sprint) { 2.0 } else { 1.0 };
+    let direction = flat_input(&actions, &transform);
+    transform.translation += direction * camera.speed * sprint * dt;
+    camera.velocity.x = 0.0;
+    camera.velocity.z = 0.0;
 
-        camera.velocity.y += gravity * dt;
-        transform.translation.y += camera.velocity.y * dt;
+    if *mode.get() == PlayerMode::Walking && actions.just_pressed(Action::Jump) {
+        camera.velocity.y = 8.0;
+    }
 
-        let terrain_h = get_height(
-            transform.translation.x as f64,
-            transform.translation.z as f64,
-            &noise,
-        );
+    let new_mode = fall_and_land(&mut transform, &mut camera, dt, &noise, &config, &edits, &colliders);
 
-        let player_height = 1.0;
-        let ground_y = terrain_h + player_height;
+    // footprints
+    let feet = transform.translation - Vec3::Y * PLAYER_EYE_HEIGHT;
+    if new_mode == PlayerMode::Walking && feet.xz().distance(last_step.xz()) > STEP_LENGTH {
+        edits.stamp(feet, Stamp::Footprint, &config);
+        *last_step = feet;
+    }
 
-        if transform.translation.y <= ground_y {
-            transform.translation.y = ground_y;
-            camera.velocity.y = 0.0;
-            camera.grounded = true;
-        }
+    if new_mode != *mode.get() {
+        switch_mode(&mut next_mode, new_mode);
     }
 }
 
-// snowball logic
+// on lake ice the player keeps sliding, the keys only push a bit
+#[allow(clippy::too_many_arguments)]
+fn skating_movement(
+    actions: Res<ActionState>,
+    time: Res<Time>,
+    noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
+    colliders: Res<ColliderGrid>,
+    edits: Res<TerrainEdits>,
+    mut next_mode: ResMut<NextState<PlayerMode>>,
+    mut query: Query<(&mut Transform, &mut FlyCamera)>,
+) {
+    let Ok((mut transform, mut camera)) = query.single_mut() else { return };
+    let dt = time.delta_secs();
 
-#[derive(Component)]
-pub struct Snowball {
-    pub velocity: Vec3,
+    let push = flat_input(&actions, &transform) * camera.speed * 1.5;
+    let friction = 0.6;
+    let mut slide = camera.velocity.with_y(0.0) + push * dt;
+    slide -= slide * friction * dt;
+    slide = slide.clamp_length_max(camera.speed * 2.0);
+    camera.velocity.x = slide.x;
+    camera.velocity.z = slide.z;
+    transform.translation += slide * dt;
+
+    if actions.just_pressed(Action::Jump) {
+        camera.velocity.y = 8.0;
+    }
+
+    let new_mode = fall_and_land(&mut transform, &mut camera, dt, &noise, &config, &edits, &colliders);
+    if new_mode != PlayerMode::Skating {
+        switch_mode(&mut next_mode, new_mode);
+    }
 }
 
-fn spawn_snowball(
-    commands: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<StandardMaterial>>,
-    cam: &Transform,
+fn flying_movement(
+    actions: Res<ActionState>,
+    time: Res<Time>,
+    mut query: Query<(&mut Transform, &FlyCamera)>,
 ) {
-    let pos = cam.translation + (*cam.forward()) * 1.0;
-    let vel = *cam.forward() * 20.0;
+    let Ok((mut transform, camera)) = query.single_mut() else { return };
 
-    commands.spawn((
-        Mesh3d(meshes.add(Sphere::new(0.1).mesh().build())),
-        MeshMaterial3d(materials.add(StandardMaterial {
-            base_color: Color::WHITE,
-            unlit: true,
-            ..default()
-        })),
-        Snowball { velocity: vel },
-        Transform::from_translation(pos),
-    ));
+    let forward = *transform.forward();
+    let right = *transform.right();
+    let mut direction = forward * actions.movement.y + right * actions.movement.x;
+
+    if actions.pressed(Action::Jump) { direction += Vec3::Y; }
+    if actions.pressed(Action::Descend) { direction -= Vec3::Y; }
+
+    transform.translation += direction.clamp_length_max(1.0) * camera.speed * 3.0 * time.delta_secs(); // * 3.0 to make flying fastern than walking
 }
 
-fn move_snowballs(
-    time: Res<Time>,
-    mut query: Query<(Entity, &mut Transform, &mut Snowball)>,
-    noise: Res<NoiseGenerators>,
-    mut commands: Commands
+// ToggleFly (F): start flying from anywhere on foot, stop flying = falling
+fn toggle_flying(
+    actions: Res<ActionState>,
+    mode: Res<State<PlayerMode>>,
+    mut next_mode: ResMut<NextState<PlayerMode>>,
+    mut query: Query<&mut FlyCamera>,
 ) {
-    let dt = time.delta_secs();
-    let gravity = 7.5;
-
-    for (entity, mut t, mut ball) in query.iter_mut() {
-        t.translation += ball.velocity * dt;
-        ball.velocity.y -= gravity * dt;
-
-        // get height of ground
-        let terrain_h = get_height(
-            t.translation.x as f64,
-            t.translation.z as f64,
-            &noise,
-        );
-        // despawn if on ground
-        if t.translation.y <= terrain_h {
-            commands.entity(entity).despawn();
-            continue;
-        }
+    if !actions.just_pressed(Action::ToggleFly) {
+        return;
+    }
+    if let Ok(mut camera) = query.single_mut() {
+        camera.velocity = Vec3::ZERO;
     }
+    next_mode.set(if *mode.get() == PlayerMode::Flying { PlayerMode::Airborne } else { PlayerMode::Flying });
 }
 
-fn handle_input(
-    mouse: Res<ButtonInput<MouseButton>>,
-    mut camera_query: Query<&mut Transform, (With<Camera3d>, Without<SledEntity>)>,
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut cam_state_query: Query<&mut FlyCamera, With<Camera3d>>,
-    sled_entity_query: Query<Entity, With<SledEntity>>,
-    sled: Res<Sled>,
+// ToggleSled (right click): get on the sled, or off again
+fn toggle_sled(
+    actions: Res<ActionState>,
+    mode: Res<State<PlayerMode>>,
+    mut next_mode: ResMut<NextState<PlayerMode>>,
 ) {
-
-    if mouse.just_pressed(MouseButton::Left) {
-        let Ok(cam) = camera_query.single() else { return };
-        spawn_snowball(
-            &mut commands,
-            &mut meshes,
-            &mut materials,
-            cam,
-        );
-    }
-
-    if mouse.just_pressed(MouseButton::Right) {
-        let Ok(cam_transform) = camera_query.single_mut() else { return };
-        let Ok(mut cam_state) = cam_state_query.single_mut() else { return };
-
-        if !cam_state.sledding {
-            spawn_sled(&mut commands, &*cam_transform, &sled, &mut cam_state);
-        } else {
-            if let Ok(sled_entity) = sled_entity_query.single() {
-                commands.entity(sled_entity).despawn();
-            }
-            cam_state.sledding = false;
-        }
+    if !actions.just_pressed(Action::ToggleSled) {
+        return;
     }
+    next_mode.set(if *mode.get() == PlayerMode::Sledding { PlayerMode::Walking } else { PlayerMode::Sledding });
 }
 
 #[derive(Resource)]
@@ -285,27 +342,29 @@ pub struct SledEntity;
 fn load_slead(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    paths: Res<AssetPaths>,
 ) {
-    let sled: Handle<Scene> = asset_server.load("sled.glb#Scene0");
+    let sled: Handle<Scene> = asset_server.load(&paths.sled);
 
     commands.insert_resource(Sled { handle: sled });
 
     println!("loaded candycane")
 }
 
+// OnEnter(Sledding)
 fn spawn_sled(
-    commands: &mut Commands,
-    cam_transform: &Transform,
-    sled: &Sled,
-    cam_state: &mut FlyCamera,
+    mut commands: Commands,
+    sled: Res<Sled>,
+    cam_query: Query<(&Transform, &FlyCamera)>,
 ) {
-    let spawn_pos = cam_transform.translation - Vec3::new(0.0, 1.75, 0.0);
+    let Ok((cam_transform, cam_state)) = cam_query.single() else { return };
+    let spawn_pos = cam_transform.translation - Vec3::Y * (SLED_SEAT_HEIGHT + PLAYER_EYE_HEIGHT);
 
     let sled_rotation = Quat::from_rotation_y(cam_state.yaw);
 
     commands.spawn((
         SledEntity,
-        SledMotion::default(),
+        SledMotion { heading: cam_state.yaw, ..default() },
         SceneRoot(sled.handle.clone()),
         Transform {
             translation: spawn_pos,
@@ -313,58 +372,149 @@ fn spawn_sled(
             scale: Vec3::splat(0.5),
         },
     ));
+}
 
-    cam_state.sledding = true;
+// OnExit(Sledding), however the ride ended
+fn despawn_sled(
+    mut commands: Commands,
+    sleds: Query<Entity, With<SledEntity>>,
+    mut cam_query: Query<&mut FlyCamera>,
+) {
+    for sled in &sleds {
+        commands.entity(sled).despawn();
+    }
+    if let Ok(mut camera) = cam_query.single_mut() {
+        camera.velocity = Vec3::ZERO;
+    }
 }
 
 #[derive(Component, Default)]
 pub struct SledMotion {
-    pub velocity: Vec3,
+    pub velocity: Vec3, // world units per second
+    pub heading: f32, // yaw the sled points to, A/D turn it
+    pub airborne: bool,
 }
 
-// helper function, so the camera sticks to the sled
+// friction under the sled: the biome, and its own old tracks are packed and faster
+fn sled_friction(pos: Vec3, noise: &NoiseGenerators, config: &WorldGenConfig, edits: &TerrainEdits) -> f32 {
+    let friction = BiomeMap::new(noise, config).weights(pos.x as f64, pos.z as f64).blend(Biome::sled_friction);
+    let packed = edits.offset_at(pos.x as f64, pos.z as f64, config) < -0.05;
+    if packed { friction * 0.6 } else { friction }
+}
+
+// A/D steer, S brakes, Jump hops. On the ground the velocity stays in the slope plane,
+// over a crest the sled keeps going straight and flies for a bit
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn sledding_system(
+    actions: Res<ActionState>,
     time: Res<Time>,
     noise: Res<NoiseGenerators>,
-    mut cam_q: Query<(&mut Transform, &mut FlyCamera), (With<Camera3d>, Without<SledEntity>)>,
-    mut sled_q: Query<(&mut Transform, &mut SledMotion), (With<SledEntity>, Without<Camera3d>)>,
+    config: Res<WorldGenConfig>,
+    colliders: Res<ColliderGrid>,
+    wind: Res<Wind>,
+    mut edits: ResMut<TerrainEdits>,
+    mut last_track: Local<Vec3>,
+    mut next_mode: ResMut<NextState<PlayerMode>>,
+    mut cam_q: Query<&mut Transform, (With<FlyCamera>, Without<SledEntity>)>,
+    mut sled_q: Query<(&mut Transform, &mut SledMotion), (With<SledEntity>, Without<FlyCamera>)>,
 ) {
-    let Ok((mut cam_t, mut cam_state)) = cam_q.single_mut() else { return };
+    let Ok(mut cam_t) = cam_q.single_mut() else { return };
+    // the sled gets spawned with the commands of OnEnter, so it can be missing for a frame
+    let Ok((mut sled_t, mut motion)) = sled_q.single_mut() else { return };
 
-    if !cam_state.sledding {
-        return;
+    let dt = time.delta_secs();
+    let gravity = Vec3::new(0.0, -SLED_GRAVITY, 0.0);
+    let normal = get_surface_normal(sled_t.translation.x as f64, sled_t.translation.z as f64, &noise, &config, &edits);
+
+    // steering works better the faster you go, standing still you can only shuffle around
+    let speed = motion.velocity.length();
+    let steer = -actions.movement.x * SLED_TURN_RATE * (0.3 + speed / 10.0).min(1.0);
+    // last frame's tilt without its yaw, taken before the heading turns
+    let last_tilt = sled_t.rotation * Quat::from_rotation_y(-motion.heading);
+    motion.heading += steer * dt;
+    let forward = Quat::from_rotation_y(motion.heading) * Vec3::NEG_Z;
+
+    if motion.airborne {
+        motion.velocity += gravity * dt;
+    } else {
+        motion.velocity += acceleration_on_slope(normal, gravity) * dt;
+        let into = motion.velocity.dot(normal);
+        motion.velocity -= normal * into;
+
+        // lateral friction: the runners only slide along the heading
+        let along_dir = (forward - normal * forward.dot(normal)).normalize_or_zero();
+        let along = motion.velocity.dot(along_dir);
+        let side = motion.velocity - along_dir * along;
+        let friction = sled_friction(sled_t.translation, &noise, &config, &edits);
+        let grip = SLED_GRIP * (friction / 0.08).clamp(0.25, 1.0); // ice lets the sled drift
+        motion.velocity = along_dir * along + side * (-grip * dt).exp();
+
+        // snow friction and braking
+        let brake = (-actions.movement.y).max(0.0) * SLED_BRAKE;
+        let decel = (friction + brake) * SLED_GRAVITY * normal.y;
+        let speed = motion.velocity.length();
+        motion.velocity = motion.velocity.normalize_or_zero() * (speed - decel * dt).max(0.0);
+
+        if actions.just_pressed(Action::Jump) {
+            motion.velocity += normal * SLED_HOP;
+            motion.airborne = true;
+        }
     }
 
-    let Ok((mut sled_t, mut motion)) = sled_q.single_mut() else {
-        // sled got despawned but state wasn't reset
-        cam_state.sledding = false;
-        return;
-    };
-
-    let dt = time.delta_secs();
+    // air drag, riding into the wind is slower (the sled's own y speed doesn't count)
+    let air = (wind.at(sled_t.translation) - motion.velocity).with_y(0.0);
+    motion.velocity += air * air.length() * SLED_DRAG * dt;
 
-    let gravity = Vec3::new(0.0, -10.0, 0.0); // match your "walking gravity" feel
-    let wx = sled_t.translation.x as f64;
-    let wz = sled_t.translation.z as f64;
+    sled_t.translation += motion.velocity * dt;
+    let terrain_h = get_surface_height(sled_t.translation.x as f64, sled_t.translation.z as f64, &noise, &config, &edits);
 
-    let normal = get_surface_normal(wx, wz, &noise);
-    let accel = acceleration_on_slope(normal, gravity);
+    if sled_t.translation.y <= terrain_h {
+        // landing (or just riding along), nothing goes into the ground
+        sled_t.translation.y = terrain_h;
+        let into = motion.velocity.dot(normal).min(0.0);
+        motion.velocity -= normal * into;
+        motion.airborne = false;
+    } else if !motion.airborne {
+        // the ground dropped away faster than the sled follows it: off the crest
+        if sled_t.translation.y > terrain_h + SLED_LIFTOFF {
+            motion.airborne = true;
+        } else {
+            sled_t.translation.y = terrain_h;
+        }
+    }
 
-    // optional damping so it doesn't accelerate forever
-    let damping = 0.98_f32;
-    motion.velocity *= damping;
+    // carve a trail behind the sled
+    if !motion.airborne && sled_t.translation.xz().distance(last_track.xz()) > SLED_TRACK_STEP {
+        edits.stamp(sled_t.translation, Stamp::SledTrack, &config);
+        *last_track = sled_t.translation;
+    }
 
-    motion.velocity += accel * dt;
-    sled_t.translation += motion.velocity * dt * 5.0;
+    // ran into a prop: bounce off, or crash when going too fast
+    if let Some(contact) = colliders.resolve(sled_t.translation, 1.0, SLED_RADIUS, &config) {
+        sled_t.translation += contact.push;
+
+        let impact = -motion.velocity.dot(contact.normal);
+        if impact > SLED_CRASH_SPEED {
+            // OnExit takes the sled away
+            switch_mode(&mut next_mode, PlayerMode::Walking);
+        } else if impact > 0.0 {
+            let restitution = 0.4;
+            let into = motion.velocity.dot(contact.normal);
+            motion.velocity -= contact.normal * into * (1.0 + restitution);
+        }
+    }
 
-    // keep sled on terrain
-    let terrain_h = get_height(sled_t.translation.x as f64, sled_t.translation.z as f64, &noise);
+    // face the heading and lean into the slope, in the air it keeps the last tilt
+    let tilt = if motion.airborne {
+        last_tilt
+    } else {
+        Quat::from_rotation_arc(Vec3::Y, normal)
+    };
+    let target = tilt * Quat::from_rotation_y(motion.heading);
+    sled_t.rotation = sled_t.rotation.slerp(target, (10.0 * dt).min(1.0));
 
-    if sled_t.translation.y < terrain_h {
-        sled_t.translation.y = terrain_h;
-    }
-    // stick camera to sled
-    cam_t.translation = sled_t.translation + Vec3::new(0.0, 1.75, 0.0);
+    // the player sits on the sled
+    cam_t.translation = sled_t.translation + Vec3::Y * (SLED_SEAT_HEIGHT + PLAYER_EYE_HEIGHT);
 }
 
 fn acceleration_on_slope(normal: Vec3, gravity: Vec3) -> Vec3 {
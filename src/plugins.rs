@@ -0,0 +1,190 @@
+use std::path::PathBuf;
+use bevy::app::PluginGroupBuilder;
+use bevy::prelude::*;
+
+use crate::asset_paths::AssetPaths;
+use crate::camera::CameraPlugin;
+use crate::cli::{LaunchOptions, DEFAULT_SEED};
+use crate::config::{ConfigOverrides, WorldGenConfigPlugin};
+#[cfg(feature = "hud")]
+use crate::hud::HudPlugin;
+use crate::input::InputActionsPlugin;
+use crate::night_sky::NightSkyPlugin;
+use crate::noise::NoiseGenerators;
+use crate::player::{PlayerPlugin, PlayerSpawn};
+use crate::save::{SaveOverrides, SavePlugin, SaveSlot};
+use crate::sky::SkyPlugin;
+use crate::snowball::SnowballPlugin;
+use crate::weather::WeatherPlugin;
+use crate::wind::WindPlugin;
+#[cfg(feature = "snow")]
+use crate::snowflake::SnowflakePlugin;
+#[cfg(feature = "debug-wireframe")]
+use crate::world::WireframeDebugPlugin;
+use crate::world::{TimeOfDay, WorldPlugin};
+use crate::world_gen::WorldGenPlugin;
+
+/// The whole winter world on top of DefaultPlugins (or MinimalPlugins + HeadlessPlugin).
+/// Snow, HUD and wireframe can only be turned on when their cargo feature is compiled in.
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use bevy_winter::WinterPlugins;
+///
+/// App::new()
+///     .add_plugins(DefaultPlugins)
+///     .add_plugins(WinterPlugins::default().seed(42).snow(false))
+///     .run();
+/// ```
+#[derive(Clone, Debug)]
+pub struct WinterPlugins {
+    seed: u32,
+    snow: bool,
+    hud: bool,
+    wireframe: bool,
+    assets: AssetPaths,
+    render_distance: Option<i32>,
+    spawn: Option<Vec2>,
+    time: Option<f32>,
+    save_dir: Option<PathBuf>,
+}
+
+impl Default for WinterPlugins {
+    fn default() -> Self {
+        Self {
+            seed: DEFAULT_SEED,
+            // everything that's compiled in is on
+            snow: cfg!(feature = "snow"),
+            hud: cfg!(feature = "hud"),
+            wireframe: cfg!(feature = "debug-wireframe"),
+            assets: AssetPaths::default(),
+            render_distance: None,
+            spawn: None,
+            time: None,
+            save_dir: None,
+        }
+    }
+}
+
+impl WinterPlugins {
+    /// Everything the command line can set.
+    pub fn from_options(options: &LaunchOptions) -> Self {
+        Self {
+            seed: options.seed,
+            snow: options.snow && cfg!(feature = "snow"),
+            render_distance: options.render_distance,
+            spawn: options.spawn,
+            time: options.time,
+            save_dir: options.save_dir(),
+            ..default()
+        }
+    }
+
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn snow(mut self, enabled: bool) -> Self {
+        self.snow = enabled;
+        self
+    }
+
+    pub fn hud(mut self, enabled: bool) -> Self {
+        self.hud = enabled;
+        self
+    }
+
+    // F3 wireframe toggle, turn it off when running without a renderer
+    pub fn wireframe(mut self, enabled: bool) -> Self {
+        self.wireframe = enabled;
+        self
+    }
+
+    pub fn assets(mut self, assets: AssetPaths) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    // overrides the render distance of the config file
+    pub fn render_distance(mut self, chunks: Option<i32>) -> Self {
+        self.render_distance = chunks;
+        self
+    }
+
+    pub fn spawn(mut self, spawn: Option<Vec2>) -> Self {
+        self.spawn = spawn;
+        self
+    }
+
+    // hour of the day to start at, also with a save
+    pub fn time(mut self, hours: Option<f32>) -> Self {
+        self.time = hours;
+        self
+    }
+
+    // None = the default save slot
+    pub fn save_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.save_dir = dir;
+        self
+    }
+}
+
+impl PluginGroup for WinterPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let optional = [
+            (self.snow, cfg!(feature = "snow"), "snow"),
+            (self.hud, cfg!(feature = "hud"), "hud"),
+            (self.wireframe, cfg!(feature = "debug-wireframe"), "debug-wireframe"),
+        ];
+        for (_, _, feature) in optional.iter().filter(|(wanted, compiled, _)| *wanted && !compiled) {
+            warn!("WinterPlugins: {feature} is turned on, but the \"{feature}\" cargo feature isn't compiled in");
+        }
+
+        let group = PluginGroupBuilder::start::<Self>()
+            .add(WinterSettingsPlugin(self.clone()))
+            .add(WorldGenConfigPlugin)
+            .add(InputActionsPlugin)
+            .add(WorldPlugin)
+            .add(WeatherPlugin)
+            .add(SkyPlugin)
+            .add(NightSkyPlugin)
+            .add(WindPlugin)
+            .add(PlayerPlugin)
+            .add(CameraPlugin)
+            .add(SnowballPlugin)
+            .add(WorldGenPlugin)
+            .add(SavePlugin);
+
+        #[cfg(feature = "snow")]
+        let group = if self.snow { group.add(SnowflakePlugin) } else { group };
+        #[cfg(feature = "hud")]
+        let group = if self.hud { group.add(HudPlugin) } else { group };
+        #[cfg(feature = "debug-wireframe")]
+        let group = if self.wireframe { group.add(WireframeDebugPlugin) } else { group };
+        group
+    }
+}
+
+/// Puts the WinterPlugins options into resources, the other plugins read them from there.
+pub struct WinterSettingsPlugin(WinterPlugins);
+
+impl Plugin for WinterSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = &self.0;
+        app.insert_resource(NoiseGenerators::new(settings.seed))
+            .insert_resource(settings.assets.clone())
+            .insert_resource(ConfigOverrides { render_distance: settings.render_distance })
+            .insert_resource(SaveOverrides { spawn: settings.spawn.is_some(), time: settings.time.is_some() });
+
+        if let Some(spawn) = settings.spawn {
+            app.insert_resource(PlayerSpawn { x: spawn.x, z: spawn.y });
+        }
+        if let Some(hours) = settings.time {
+            app.insert_resource(TimeOfDay { hours });
+        }
+        if let Some(dir) = &settings.save_dir {
+            app.insert_resource(SaveSlot { dir: dir.clone() });
+        }
+    }
+}
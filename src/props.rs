@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use bevy::prelude::*;
+use bevy::gltf::{Gltf, GltfMesh, GltfNode};
+
+use crate::biome::PropKind;
+use crate::world_gen::PropPlacement;
+use crate::asset_paths::AssetPaths;
+
+// batched prop rendering: the meshes and materials get pulled out of the glb files once,
+// then every chunk merges all its props of one material into a single mesh
+
+/// One material of a prop model, with the glb node transforms already baked into the mesh.
+pub struct PropPart {
+    pub mesh: Mesh,
+    pub material: Handle<StandardMaterial>,
+}
+
+/// The extracted prop models, shared with the chunk tasks.
+#[derive(Resource, Clone, Default)]
+pub struct PropModels(pub Arc<HashMap<PropKind, Vec<PropPart>>>);
+
+#[derive(Resource)]
+pub struct PropGltfs {
+    pub tree: Handle<Gltf>,
+    pub candy_cane: Handle<Gltf>,
+}
+
+pub fn load_prop_gltfs(mut commands: Commands, asset_server: Res<AssetServer>, paths: Res<AssetPaths>) {
+    commands.insert_resource(PropGltfs {
+        tree: asset_server.load(&paths.tree),
+        candy_cane: asset_server.load(&paths.candy_cane),
+    });
+}
+
+// waits until the glb files are loaded, then builds PropModels (runs until it exists)
+#[allow(clippy::too_many_arguments)]
+pub fn extract_prop_models(
+    mut commands: Commands,
+    gltfs: Res<PropGltfs>,
+    asset_server: Res<AssetServer>,
+    gltf_assets: Res<Assets<Gltf>>,
+    gltf_nodes: Res<Assets<GltfNode>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+    meshes: Res<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !asset_server.is_loaded_with_dependencies(&gltfs.tree)
+        || !asset_server.is_loaded_with_dependencies(&gltfs.candy_cane)
+    {
+        return;
+    }
+    let (Some(tree), Some(candy_cane)) = (gltf_assets.get(&gltfs.tree), gltf_assets.get(&gltfs.candy_cane)) else {
+        return;
+    };
+
+    let mut models = HashMap::new();
+    models.insert(PropKind::Tree, gltf_parts(tree, &gltf_nodes, &gltf_meshes, &meshes));
+    models.insert(PropKind::CandyCane, gltf_parts(candy_cane, &gltf_nodes, &gltf_meshes, &meshes));
+    models.insert(PropKind::Shrub, shrub_parts(&mut materials));
+
+    commands.insert_resource(PropModels(Arc::new(models)));
+    info!("prop models ready");
+}
+
+// walks the node tree of a glb and bakes every mesh primitive into one mesh per material
+fn gltf_parts(
+    gltf: &Gltf,
+    gltf_nodes: &Assets<GltfNode>,
+    gltf_meshes: &Assets<GltfMesh>,
+    meshes: &Assets<Mesh>,
+) -> Vec<PropPart> {
+    // root nodes are the ones that aren't a child of anything
+    let children: Vec<_> = gltf.nodes.iter()
+        .filter_map(|h| gltf_nodes.get(h))
+        .flat_map(|n| n.children.iter().map(|c| c.id()))
+        .collect();
+    let mut stack: Vec<(Handle<GltfNode>, Transform)> = gltf.nodes.iter()
+        .filter(|h| !children.contains(&h.id()))
+        .map(|h| (h.clone(), Transform::IDENTITY))
+        .collect();
+
+    let mut parts: Vec<PropPart> = Vec::new();
+    while let Some((handle, parent_transform)) = stack.pop() {
+        let Some(node) = gltf_nodes.get(&handle) else { continue };
+        let transform = parent_transform.mul_transform(node.transform);
+
+        let primitives = node.mesh.as_ref()
+            .and_then(|m| gltf_meshes.get(m))
+            .map(|m| m.primitives.as_slice())
+            .unwrap_or_default();
+
+        for primitive in primitives {
+            let Some(mesh) = meshes.get(&primitive.mesh) else { continue };
+            let material = primitive.material.clone().unwrap_or_default();
+            let mesh = mesh.clone().transformed_by(transform);
+
+            // Mesh::merge drops attributes the other mesh doesn't have, so only the same layout gets merged
+            match parts.iter_mut().find(|p| p.material == material && same_layout(&p.mesh, &mesh)) {
+                Some(part) => {
+                    if let Err(err) = part.mesh.merge(&mesh) {
+                        warn!("could not merge prop mesh: {err}");
+                    }
+                }
+                None => parts.push(PropPart { mesh, material }),
+            }
+        }
+
+        stack.extend(node.children.iter().map(|c| (c.clone(), transform)));
+    }
+
+    parts
+}
+
+// same vertex attributes in the same formats
+fn same_layout(a: &Mesh, b: &Mesh) -> bool {
+    let layout = |mesh: &Mesh| mesh.attributes().map(|(attr, _)| (attr.id, attr.format)).collect::<Vec<_>>();
+    layout(a) == layout(b)
+}
+
+// tundra shrubs, there's no model for them so it's just a ball that the placement squashes
+fn shrub_parts(materials: &mut Assets<StandardMaterial>) -> Vec<PropPart> {
+    vec![PropPart {
+        mesh: Sphere::new(0.6).mesh().ico(2).unwrap(),
+        material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.32, 0.36, 0.26),
+            perceptual_roughness: 0.9,
+            ..default()
+        }),
+    }]
+}
+
+/// Merges all placements of a chunk into one mesh per prop material. Runs inside the chunk task.
+pub fn merge_props(props: &[PropPlacement], models: &PropModels) -> Vec<(Handle<StandardMaterial>, Mesh)> {
+    let mut merged: Vec<(Handle<StandardMaterial>, Mesh)> = Vec::new();
+
+    for (kind, parts) in models.0.iter() {
+        for part in parts {
+            let mut batch: Option<Mesh> = None;
+
+            for prop in props.iter().filter(|p| p.kind == *kind) {
+                let mesh = part.mesh.clone().transformed_by(prop.transform);
+                match batch.as_mut() {
+                    Some(batch) => {
+                        if let Err(err) = batch.merge(&mesh) {
+                            warn!("could not merge prop mesh: {err}");
+                        }
+                    }
+                    None => batch = Some(mesh),
+                }
+            }
+
+            if let Some(batch) = batch {
+                merged.push((part.material.clone(), batch));
+            }
+        }
+    }
+
+    merged
+}
@@ -0,0 +1,321 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::chunks::get_height;
+use crate::config::{TerrainChanged, WorldGenConfig};
+use crate::deform::TerrainEdits;
+use crate::input::{Action, ActionState};
+use crate::noise::NoiseGenerators;
+use crate::player::{FlyCamera, PlayerMode};
+use crate::world::TimeOfDay;
+use crate::world_gen::{regenerate_chunks, chunk_system, ChunkCoord};
+
+// world saves: saves/<name>/world.ron has the seed, player and time,
+// regions/r.<x>.<z>.ron hold the chunk edits of REGION_SIZE x REGION_SIZE chunks each.
+// F5 writes a snapshot, the save gets loaded on startup and regions get read when the player gets close
+
+pub const SAVE_VERSION: u32 = 1;
+pub const DEFAULT_SAVE_DIR: &str = "saves/world";
+pub const REGION_SIZE: i32 = 16; // chunks per region side
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RegionCoord {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl RegionCoord {
+    pub fn of(chunk: ChunkCoord) -> Self {
+        Self { x: chunk.x.div_euclid(REGION_SIZE), z: chunk.z.div_euclid(REGION_SIZE) }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorldSave {
+    pub version: u32,
+    pub seed: u32,
+    pub player: PlayerSave,
+    pub time_of_day: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerSave {
+    pub translation: [f32; 3],
+    pub pitch: f32,
+    pub yaw: f32,
+    pub speed: f32,
+    pub flying: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegionSave {
+    pub version: u32,
+    pub chunk_size: usize, // edits are per vertex, they only fit the same chunk size
+    pub chunks: Vec<ChunkSave>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChunkSave {
+    pub x: i32,
+    pub z: i32,
+    pub offsets: Vec<f32>,
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("could not access save file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse save file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not write save file: {0}")]
+    Write(#[from] ron::Error),
+    #[error("save version {0} is not supported (expected {SAVE_VERSION})")]
+    Version(u32),
+}
+
+/// Where the current world gets saved to.
+#[derive(Resource)]
+pub struct SaveSlot {
+    pub dir: PathBuf,
+}
+
+impl Default for SaveSlot {
+    fn default() -> Self {
+        Self { dir: PathBuf::from(DEFAULT_SAVE_DIR) }
+    }
+}
+
+impl SaveSlot {
+    fn world_path(&self) -> PathBuf {
+        self.dir.join("world.ron")
+    }
+
+    fn region_path(&self, region: RegionCoord) -> PathBuf {
+        self.dir.join("regions").join(format!("r.{}.{}.ron", region.x, region.z))
+    }
+}
+
+/// What the launch options set explicitly, that wins over the save.
+#[derive(Resource, Default, Clone, Debug)]
+pub struct SaveOverrides {
+    pub spawn: bool,
+    pub time: bool,
+}
+
+// regions whose file got read already (or that have no file)
+#[derive(Resource, Default)]
+pub struct LoadedRegions {
+    pub regions: HashSet<RegionCoord>,
+}
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveSlot>()
+            .init_resource::<SaveOverrides>()
+            .init_resource::<TimeOfDay>()
+            .init_resource::<LoadedRegions>()
+            .add_systems(PostStartup, load_world)
+            .add_systems(Update, (
+                // a new vertex grid throws away the edits, they have to be read again
+                forget_regions,
+                load_regions,
+            ).chain().after(regenerate_chunks).before(chunk_system))
+            .add_systems(Update, save_on_hotkey);
+    }
+}
+
+fn read_ron<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, SaveError> {
+    let text = fs::read_to_string(path)?;
+    Ok(ron::from_str(&text)?)
+}
+
+fn write_ron<T: Serialize>(path: &Path, value: &T) -> Result<(), SaveError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let text = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?;
+    fs::write(path, text)?;
+    Ok(())
+}
+
+pub fn read_world(slot: &SaveSlot) -> Result<WorldSave, SaveError> {
+    let save: WorldSave = read_ron(&slot.world_path())?;
+    if save.version != SAVE_VERSION {
+        return Err(SaveError::Version(save.version));
+    }
+    Ok(save)
+}
+
+pub fn read_region(slot: &SaveSlot, region: RegionCoord) -> Result<RegionSave, SaveError> {
+    let save: RegionSave = read_ron(&slot.region_path(region))?;
+    if save.version != SAVE_VERSION {
+        return Err(SaveError::Version(save.version));
+    }
+    Ok(save)
+}
+
+/// Writes world.ron and one file for every region with edits.
+pub fn write_snapshot(
+    slot: &SaveSlot,
+    world: &WorldSave,
+    edits: &TerrainEdits,
+    config: &WorldGenConfig,
+) -> Result<(), SaveError> {
+    write_ron(&slot.world_path(), world)?;
+
+    let mut regions: HashMap<RegionCoord, Vec<ChunkSave>> = HashMap::new();
+    for (coord, offsets) in &edits.chunks {
+        regions.entry(RegionCoord::of(*coord)).or_default().push(ChunkSave {
+            x: coord.x,
+            z: coord.z,
+            offsets: offsets.to_vec(),
+        });
+    }
+    for (region, mut chunks) in regions {
+        chunks.sort_by_key(|c| (c.z, c.x)); // same edits = same file
+        let save = RegionSave { version: SAVE_VERSION, chunk_size: config.chunk_size, chunks };
+        write_ron(&slot.region_path(region), &save)?;
+    }
+    Ok(())
+}
+
+// restores seed, player and time from the save, if there is one.
+// --spawn and --time (or the WinterPlugins options) win over the save
+fn load_world(
+    slot: Res<SaveSlot>,
+    overrides: Res<SaveOverrides>,
+    config: Res<WorldGenConfig>,
+    mut noise: ResMut<NoiseGenerators>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut next_mode: ResMut<NextState<PlayerMode>>,
+    mut camera_query: Query<(&mut Transform, &mut FlyCamera)>,
+) {
+    if !slot.world_path().exists() {
+        return;
+    }
+    let save = match read_world(&slot) {
+        Ok(save) => save,
+        Err(err) => {
+            warn!("could not load {}: {err}", slot.world_path().display());
+            return;
+        }
+    };
+
+    *noise = NoiseGenerators::new(save.seed);
+    if overrides.time {
+        info!("starting at {:.1}h instead of the saved {:.1}h", time_of_day.hours, save.time_of_day);
+    } else {
+        time_of_day.hours = save.time_of_day;
+    }
+
+    if let Ok((mut transform, mut camera)) = camera_query.single_mut() {
+        if overrides.spawn {
+            // the spawn height came from the seed before the save, put the player onto this terrain
+            let ground = get_height(transform.translation.x as f64, transform.translation.z as f64, &noise, &config);
+            transform.translation.y = ground + 1.5;
+            next_mode.set(PlayerMode::Airborne);
+            info!("spawning at the given position instead of the saved one");
+        } else {
+            transform.translation = Vec3::from_array(save.player.translation);
+            transform.rotation = Quat::from_rotation_y(save.player.yaw) * Quat::from_rotation_x(save.player.pitch);
+            camera.pitch = save.player.pitch;
+            camera.yaw = save.player.yaw;
+            camera.speed = save.player.speed;
+            // falls onto the ground (or the ice) from there
+            next_mode.set(if save.player.flying { PlayerMode::Flying } else { PlayerMode::Airborne });
+        }
+    }
+    info!("loaded world from {}", slot.dir.display());
+}
+
+fn forget_regions(mut terrain_changed: EventReader<TerrainChanged>, mut loaded: ResMut<LoadedRegions>) {
+    if terrain_changed.read().any(|change| change.grid) {
+        loaded.regions.clear();
+    }
+}
+
+// reads the region files around the player before chunk_system builds the chunks in them
+fn load_regions(
+    slot: Res<SaveSlot>,
+    config: Res<WorldGenConfig>,
+    mut loaded: ResMut<LoadedRegions>,
+    mut edits: ResMut<TerrainEdits>,
+    player_query: Query<&GlobalTransform, With<FlyCamera>>,
+) {
+    let Ok(player_transform) = player_query.single() else { return };
+    let player_chunk = config.chunk_coord(player_transform.translation());
+    let rd = config.render_distance;
+    let min = RegionCoord::of(ChunkCoord { x: player_chunk.x - rd, z: player_chunk.z - rd });
+    let max = RegionCoord::of(ChunkCoord { x: player_chunk.x + rd, z: player_chunk.z + rd });
+
+    for rz in min.z..=max.z {
+        for rx in min.x..=max.x {
+            let region = RegionCoord { x: rx, z: rz };
+            if !loaded.regions.insert(region) || !slot.region_path(region).exists() {
+                continue;
+            }
+
+            let save = match read_region(&slot, region) {
+                Ok(save) => save,
+                Err(err) => {
+                    warn!("could not load region {rx} {rz}: {err}");
+                    continue;
+                }
+            };
+            let size = config.chunk_size * config.chunk_size;
+            if save.chunk_size != config.chunk_size {
+                warn!("region {rx} {rz} was saved with chunk size {}, skipping it", save.chunk_size);
+                continue;
+            }
+
+            for chunk in save.chunks.into_iter().filter(|c| c.offsets.len() == size) {
+                let coord = ChunkCoord { x: chunk.x, z: chunk.z };
+                // edits made this session win over the file
+                edits.chunks.entry(coord).or_insert_with(|| Arc::new(chunk.offsets));
+                edits.dirty.insert(coord);
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_on_hotkey(
+    actions: Res<ActionState>,
+    slot: Res<SaveSlot>,
+    noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
+    edits: Res<TerrainEdits>,
+    time_of_day: Res<TimeOfDay>,
+    mode: Res<State<PlayerMode>>,
+    camera_query: Query<(&Transform, &FlyCamera)>,
+) {
+    if !actions.just_pressed(Action::Save) {
+        return;
+    }
+    let Ok((transform, camera)) = camera_query.single() else { return };
+
+    let world = WorldSave {
+        version: SAVE_VERSION,
+        seed: noise.seed,
+        player: PlayerSave {
+            translation: transform.translation.to_array(),
+            pitch: camera.pitch,
+            yaw: camera.yaw,
+            speed: camera.speed,
+            flying: *mode.get() == PlayerMode::Flying,
+        },
+        time_of_day: time_of_day.hours,
+    };
+
+    match write_snapshot(&slot, &world, &edits, &config) {
+        Ok(()) => info!("saved world to {}", slot.dir.display()),
+        Err(err) => error!("saving failed: {err}"),
+    }
+}
@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+use crate::config::WorldGenConfig;
+use crate::weather::{update_weather, Weather};
+use crate::world::{Moon, Sun, TimeOfDay, MOON_ILLUMINANCE, SUN_ILLUMINANCE};
+
+// day and night: the clock runs with config.day_length, the sun goes round once a day and the moon is on
+// the other side. the palettes tint whatever the weather says, so noon looks like the weather alone
+// and a blizzard at night is still dark
+
+#[derive(Clone, Copy, Debug)]
+struct Palette {
+    sky: Vec3, // tint on the weather's sky colour
+    fog: Vec3, // tint on the weather's fog colour
+    ambient: Vec3, // srgb
+    ambient_brightness: f32,
+    sun: Vec3, // sunlight colour, srgb
+    snow: f32, // brightness of the snowflakes
+}
+
+impl Palette {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            sky: self.sky.lerp(other.sky, t),
+            fog: self.fog.lerp(other.fog, t),
+            ambient: self.ambient.lerp(other.ambient, t),
+            ambient_brightness: self.ambient_brightness.lerp(other.ambient_brightness, t),
+            sun: self.sun.lerp(other.sun, t),
+            snow: self.snow.lerp(other.snow, t),
+        }
+    }
+}
+
+const NIGHT: Palette = Palette {
+    sky: Vec3::new(0.06, 0.08, 0.18),
+    fog: Vec3::new(0.08, 0.1, 0.2),
+    ambient: Vec3::new(0.5, 0.6, 1.0),
+    ambient_brightness: 20.0,
+    sun: Vec3::new(1.0, 0.5, 0.3),
+    snow: 0.15,
+};
+const DAWN: Palette = Palette {
+    sky: Vec3::new(1.0, 0.72, 0.62),
+    fog: Vec3::new(0.95, 0.76, 0.7),
+    ambient: Vec3::new(1.0, 0.8, 0.72),
+    ambient_brightness: 50.0,
+    sun: Vec3::new(1.0, 0.65, 0.45),
+    snow: 0.6,
+};
+const DAY: Palette = Palette {
+    sky: Vec3::ONE,
+    fog: Vec3::ONE,
+    ambient: Vec3::ONE,
+    ambient_brightness: 80.0,
+    sun: Vec3::ONE,
+    snow: 1.0,
+};
+const DUSK: Palette = Palette {
+    sky: Vec3::new(1.0, 0.6, 0.5),
+    fog: Vec3::new(0.9, 0.64, 0.6),
+    ambient: Vec3::new(1.0, 0.7, 0.6),
+    ambient_brightness: 45.0,
+    sun: Vec3::new(1.0, 0.55, 0.35),
+    snow: 0.5,
+};
+
+// (hour, palette), the palettes blend between the neighbours
+const KEYFRAMES: [(f32, Palette); 8] = [
+    (0.0, NIGHT),
+    (5.0, NIGHT),
+    (6.5, DAWN),
+    (8.5, DAY),
+    (16.0, DAY),
+    (18.0, DUSK),
+    (19.5, NIGHT),
+    (24.0, NIGHT),
+];
+
+fn palette_at(hours: f32) -> Palette {
+    let hours = hours.rem_euclid(24.0);
+    for pair in KEYFRAMES.windows(2) {
+        let ((from, a), (to, b)) = (pair[0], pair[1]);
+        if hours <= to {
+            return a.lerp(b, (hours - from) / (to - from));
+        }
+    }
+    NIGHT
+}
+
+/// Direction towards the sun: rises in the east at 6, highest (a bit south) at 12, sets in the west at 18.
+pub fn sun_direction(hours: f32) -> Vec3 {
+    let angle = (hours - 6.0) / 24.0 * TAU;
+    Vec3::new(angle.cos(), angle.sin(), 0.35).normalize()
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Colours of the sky right now, time of day and weather together. The fog and the snowflakes read this.
+#[derive(Resource, Clone, Debug)]
+pub struct SkyLight {
+    pub sky: Vec3, // srgb
+    pub fog: Vec3, // srgb
+    pub snow: f32, // snowflake brightness, 1 at noon
+    pub night: f32, // 0 while the sun is up, 1 once it's well below the horizon
+}
+
+impl Default for SkyLight {
+    fn default() -> Self {
+        Self { sky: Vec3::new(0.68, 0.85, 0.9), fog: Vec3::new(0.8, 0.9, 1.0), snow: 1.0, night: 0.0 }
+    }
+}
+
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeOfDay>()
+            .init_resource::<SkyLight>()
+            .add_systems(PreUpdate, (advance_time, update_sky).chain().after(update_weather));
+    }
+}
+
+fn advance_time(time: Res<Time>, config: Res<WorldGenConfig>, mut time_of_day: ResMut<TimeOfDay>) {
+    // 0 stops the clock
+    if config.day_length > 0.0 {
+        time_of_day.hours = (time_of_day.hours + time.delta_secs() / config.day_length * 24.0).rem_euclid(24.0);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_sky(
+    time_of_day: Res<TimeOfDay>,
+    weather: Res<Weather>,
+    mut sky_light: ResMut<SkyLight>,
+    mut clear_color: ResMut<ClearColor>,
+    ambient: Option<ResMut<AmbientLight>>, // not there without the renderer
+    mut suns: Query<(&mut DirectionalLight, &mut Transform), (With<Sun>, Without<Moon>)>,
+    mut moons: Query<(&mut DirectionalLight, &mut Transform), (With<Moon>, Without<Sun>)>,
+) {
+    let palette = palette_at(time_of_day.hours);
+    let params = &weather.params;
+
+    sky_light.sky = params.sky_color * palette.sky;
+    sky_light.fog = params.fog_color * palette.fog;
+    sky_light.snow = palette.snow;
+    let to_sun = sun_direction(time_of_day.hours);
+    sky_light.night = 1.0 - smoothstep(-0.2, 0.05, to_sun.y);
+    let sky = sky_light.sky;
+    clear_color.0 = Color::srgb(sky.x, sky.y, sky.z);
+
+    if let Some(mut ambient) = ambient {
+        let color = palette.ambient;
+        ambient.color = Color::srgb(color.x, color.y, color.z);
+        ambient.brightness = palette.ambient_brightness * params.sun.max(0.5);
+    }
+
+    // fades out just below the horizon, the light doesn't switch off with a pop
+    for (mut light, mut transform) in &mut suns {
+        light.illuminance = SUN_ILLUMINANCE * params.sun * smoothstep(-0.05, 0.15, to_sun.y);
+        light.color = Color::srgb(palette.sun.x, palette.sun.y, palette.sun.z);
+        light.shadows_enabled = light.illuminance > 0.0;
+        *transform = Transform::IDENTITY.looking_to(-to_sun, Vec3::Y);
+    }
+    for (mut light, mut transform) in &mut moons {
+        light.illuminance = MOON_ILLUMINANCE * params.sun * smoothstep(-0.05, 0.15, -to_sun.y);
+        *transform = Transform::IDENTITY.looking_to(to_sun, Vec3::Y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_is_up_at_noon_and_down_at_midnight() {
+        assert!(sun_direction(12.0).y > 0.9);
+        assert!(sun_direction(0.0).y < -0.9);
+        assert!(sun_direction(6.0).y.abs() < 1e-5 && sun_direction(6.0).x > 0.9); // east
+        assert!(palette_at(12.0).sky.abs_diff_eq(DAY.sky, 1e-5));
+        assert!(palette_at(2.0).sky.abs_diff_eq(NIGHT.sky, 1e-5));
+        assert!(palette_at(26.0).sky.abs_diff_eq(NIGHT.sky, 1e-5));
+        let evening = palette_at(18.75);
+        assert!(evening.snow < DUSK.snow && evening.snow > NIGHT.snow);
+    }
+}
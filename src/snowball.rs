@@ -0,0 +1,537 @@
+use bevy::prelude::*;
+use rand::{Rng, thread_rng};
+
+use crate::biome::PropKind;
+use crate::chunks::{get_surface_height, get_surface_normal};
+use crate::collision::{ColliderGrid, PropCollider};
+use crate::config::WorldGenConfig;
+use crate::deform::{Stamp, TerrainEdits};
+use crate::input::{Action, ActionState};
+use crate::noise::NoiseGenerators;
+use crate::player::{FlyCamera, PlayerMode, PLAYER_EYE_HEIGHT};
+use crate::wind::Wind;
+
+// snowballs: throwing, flying, and what happens when they hit something.
+// every hit sends a SnowballHit, the effects and the score just listen to those.
+// holding Throw charges the throw and shows where the ball will go, Scoop refills the supply
+
+pub const SNOWBALL_RADIUS: f32 = 0.1;
+const SNOWBALL_GRAVITY: f32 = 7.5;
+const SNOWBALL_DRAG: f32 = 0.004; // quadratic, against the air
+const BURST_PARTICLES: usize = 14;
+const DECAL_LIFETIME: f32 = 8.0; // seconds until a splat is gone
+const PREVIEW_DOTS: usize = 32;
+const FLIGHT_STEP: f32 = 1.0 / 60.0; // the balls and the preview both fly in these steps, so they agree
+const MAX_FLIGHT_STEPS: usize = 8; // per frame, a long hitch slows the balls down instead of piling up
+const PREVIEW_STEPS_PER_DOT: usize = 3;
+
+#[derive(Component)]
+pub struct Snowball {
+    pub velocity: Vec3,
+}
+
+/// Anything that isn't terrain or a prop and should get hit by snowballs, e.g. other players or targets.
+#[derive(Component)]
+pub struct SnowballTarget {
+    pub radius: f32, // sphere around the GlobalTransform
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HitTarget {
+    Terrain,
+    Prop(PropCollider),
+    Actor(Entity),
+}
+
+/// Sent every time a snowball hits something, right before it gets despawned.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SnowballHit {
+    pub position: Vec3,
+    pub normal: Vec3, // surface normal where it hit
+    pub target: HitTarget,
+}
+
+/// How throwing feels. `max_supply: None` means endless snowballs.
+#[derive(Resource, Clone, Debug)]
+pub struct SnowballSettings {
+    pub min_speed: f32, // a quick tap
+    pub max_speed: f32, // fully charged
+    pub charge_time: f32, // seconds to full charge
+    pub max_arc: f32, // radians the throw goes up at full charge
+    pub cooldown: f32,
+    pub max_supply: Option<u32>,
+    pub scoop_time: f32, // seconds of scooping per snowball
+}
+
+impl Default for SnowballSettings {
+    fn default() -> Self {
+        Self {
+            min_speed: 12.0,
+            max_speed: 32.0,
+            charge_time: 1.0,
+            max_arc: 0.2,
+            cooldown: 0.35,
+            max_supply: Some(10),
+            scoop_time: 0.5,
+        }
+    }
+}
+
+/// The player's throwing state: charge, cooldown, how many snowballs are left.
+#[derive(Resource, Debug)]
+pub struct SnowballThrower {
+    pub charge: Option<f32>, // seconds held, None = not charging
+    pub cooldown: f32,
+    pub supply: u32, // only counts with a max_supply
+    pub velocity: Vec3, // of the player (or the sled), the ball gets it on top
+    last_position: Option<Vec3>,
+    scooping: f32,
+}
+
+impl FromWorld for SnowballThrower {
+    fn from_world(world: &mut World) -> Self {
+        let settings = world.get_resource_or_init::<SnowballSettings>();
+        Self {
+            charge: None,
+            cooldown: 0.0,
+            supply: settings.max_supply.unwrap_or(0),
+            velocity: Vec3::ZERO,
+            last_position: None,
+            scooping: 0.0,
+        }
+    }
+}
+
+impl SnowballThrower {
+    fn has_snowball(&self, settings: &SnowballSettings) -> bool {
+        settings.max_supply.is_none() || self.supply > 0
+    }
+}
+
+/// Points from hitting things, a prop is worth 1 and an actor 5.
+#[derive(Resource, Default, Debug)]
+pub struct SnowballScore {
+    pub hits: u32,
+    pub points: u32,
+}
+
+// shared meshes and materials, so a throw doesn't create new assets every time
+#[derive(Resource)]
+struct SnowballAssets {
+    ball: Handle<Mesh>,
+    particle: Handle<Mesh>,
+    splat: Handle<Mesh>,
+    snow: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct SnowParticle {
+    velocity: Vec3,
+    gravity: f32,
+    life: f32,
+    lifetime: f32,
+}
+
+#[derive(Component)]
+struct SnowSplat {
+    age: f32,
+}
+
+#[derive(Component)]
+struct TrajectoryDot;
+
+pub struct SnowballPlugin;
+
+impl Plugin for SnowballPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SnowballScore>()
+            .init_resource::<SnowballSettings>()
+            .init_resource::<SnowballThrower>()
+            .add_event::<SnowballHit>()
+            .add_systems(Startup, (setup_snowball_assets, spawn_trajectory_dots).chain())
+            .add_systems(Update, (
+                track_thrower_velocity,
+                scoop_snow,
+                throw_snowball,
+                trajectory_preview,
+                move_snowballs,
+                (impact_burst, splat_decal, knock_snow_off_trees, count_score),
+                (update_particles, fade_splats),
+            ).chain());
+    }
+}
+
+fn setup_snowball_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(SnowballAssets {
+        ball: meshes.add(Sphere::new(SNOWBALL_RADIUS).mesh().build()),
+        particle: meshes.add(Sphere::new(0.04).mesh().ico(1).unwrap()),
+        splat: meshes.add(Circle::new(0.35)),
+        snow: materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+// the dots of the preview, hidden until the player charges a throw
+fn spawn_trajectory_dots(mut commands: Commands, assets: Res<SnowballAssets>) {
+    for _ in 0..PREVIEW_DOTS {
+        commands.spawn((
+            TrajectoryDot,
+            Mesh3d(assets.particle.clone()),
+            MeshMaterial3d(assets.snow.clone()),
+            Transform::default(),
+            Visibility::Hidden,
+        ));
+    }
+}
+
+// one step of snowball flight, the preview uses exactly the same.
+// drag works against the moving air, so a ball without speed gets carried along with the wind
+fn integrate(position: &mut Vec3, velocity: &mut Vec3, wind: &Wind, dt: f32) {
+    *position += *velocity * dt;
+    let air = wind.at(*position) - *velocity;
+    *velocity += air * air.length() * SNOWBALL_DRAG * dt;
+    velocity.y -= SNOWBALL_GRAVITY * dt;
+}
+
+// start position and velocity of a throw after charging for `charge` seconds
+fn launch(eyes: &Transform, charge: f32, thrower_velocity: Vec3, settings: &SnowballSettings) -> (Vec3, Vec3) {
+    let strength = (charge / settings.charge_time).clamp(0.0, 1.0);
+    let speed = settings.min_speed.lerp(settings.max_speed, strength);
+    // harder throws go up a bit more, so they also go further
+    let dir = Quat::from_axis_angle(*eyes.right(), settings.max_arc * strength) * *eyes.forward();
+    (eyes.translation + *eyes.forward(), dir * speed + thrower_velocity)
+}
+
+// where the player is going, from the position change, so it works on foot, skating and on the sled
+fn track_thrower_velocity(
+    time: Res<Time>,
+    mut thrower: ResMut<SnowballThrower>,
+    player_query: Query<&Transform, With<FlyCamera>>,
+) {
+    let Ok(player) = player_query.single() else { return };
+    let dt = time.delta_secs();
+    if let Some(last) = thrower.last_position.filter(|_| dt > 0.0) {
+        // a teleport (loading a save) shouldn't turn into a rocket throw
+        thrower.velocity = ((player.translation - last) / dt).clamp_length_max(40.0);
+    }
+    thrower.last_position = Some(player.translation);
+}
+
+// holding Scoop on snow picks up new snowballs, and leaves a dent
+#[allow(clippy::too_many_arguments)]
+fn scoop_snow(
+    actions: Res<ActionState>,
+    time: Res<Time>,
+    settings: Res<SnowballSettings>,
+    mode: Res<State<PlayerMode>>,
+    config: Res<WorldGenConfig>,
+    mut edits: ResMut<TerrainEdits>,
+    mut thrower: ResMut<SnowballThrower>,
+    player_query: Query<&Transform, With<FlyCamera>>,
+) {
+    let Some(max) = settings.max_supply else { return };
+    // only on the ground, and there is no snow on the lake ice
+    if !actions.pressed(Action::Scoop) || *mode.get() != PlayerMode::Walking || thrower.supply >= max {
+        thrower.scooping = 0.0;
+        return;
+    }
+    let Ok(player) = player_query.single() else { return };
+
+    thrower.scooping += time.delta_secs();
+    if thrower.scooping >= settings.scoop_time {
+        thrower.scooping = 0.0;
+        thrower.supply += 1;
+        let feet = player.translation - Vec3::Y * PLAYER_EYE_HEIGHT;
+        let front = feet + player.forward().with_y(0.0).normalize_or_zero() * 0.6;
+        edits.stamp(front, Stamp::Footprint, &config);
+    }
+}
+
+// press to start charging, release to throw
+fn throw_snowball(
+    actions: Res<ActionState>,
+    time: Res<Time>,
+    assets: Res<SnowballAssets>,
+    settings: Res<SnowballSettings>,
+    mut thrower: ResMut<SnowballThrower>,
+    camera_query: Query<&Transform, With<FlyCamera>>,
+    mut commands: Commands,
+) {
+    let dt = time.delta_secs();
+    thrower.cooldown = (thrower.cooldown - dt).max(0.0);
+
+    if actions.just_pressed(Action::Throw) && thrower.cooldown <= 0.0 && thrower.has_snowball(&settings) {
+        thrower.charge = Some(0.0);
+    }
+    let Some(charge) = thrower.charge else { return };
+    if actions.pressed(Action::Throw) {
+        thrower.charge = Some(charge + dt);
+        return;
+    }
+
+    // let go (or the binding changed while holding)
+    thrower.charge = None;
+    let Ok(cam) = camera_query.single() else { return };
+    let (position, velocity) = launch(cam, charge, thrower.velocity, &settings);
+
+    commands.spawn((
+        Mesh3d(assets.ball.clone()),
+        MeshMaterial3d(assets.snow.clone()),
+        Snowball { velocity },
+        Transform::from_translation(position),
+    ));
+    thrower.cooldown = settings.cooldown;
+    if settings.max_supply.is_some() {
+        thrower.supply = thrower.supply.saturating_sub(1);
+    }
+}
+
+// flies a pretend snowball and puts a dot every few steps, until it hits the ground or a prop
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn trajectory_preview(
+    thrower: Res<SnowballThrower>,
+    wind: Res<Wind>,
+    settings: Res<SnowballSettings>,
+    noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
+    edits: Res<TerrainEdits>,
+    colliders: Res<ColliderGrid>,
+    camera_query: Query<&Transform, With<FlyCamera>>,
+    mut dots: Query<(&mut Transform, &mut Visibility), (With<TrajectoryDot>, Without<FlyCamera>)>,
+) {
+    let path = thrower.charge.zip(camera_query.single().ok()).map(|(charge, cam)| {
+        let (mut position, mut velocity) = launch(cam, charge, thrower.velocity, &settings);
+        let mut points = Vec::with_capacity(PREVIEW_DOTS);
+        'flight: while points.len() < PREVIEW_DOTS {
+            for _ in 0..PREVIEW_STEPS_PER_DOT {
+                integrate(&mut position, &mut velocity, &wind, FLIGHT_STEP);
+                let ground = get_surface_height(position.x as f64, position.z as f64, &noise, &config, &edits);
+                if position.y <= ground || colliders.hit(position, SNOWBALL_RADIUS, &config).is_some() {
+                    break 'flight;
+                }
+            }
+            points.push(position);
+        }
+        points
+    }).unwrap_or_default();
+
+    for (i, (mut transform, mut visibility)) in dots.iter_mut().enumerate() {
+        match path.get(i) {
+            Some(point) => {
+                transform.translation = *point;
+                *visibility = Visibility::Inherited;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+// fixed steps no matter the frame rate, the leftover time waits for the next frame
+#[allow(clippy::too_many_arguments)]
+fn move_snowballs(
+    time: Res<Time>,
+    mut unstepped: Local<f32>,
+    mut query: Query<(Entity, &mut Transform, &mut Snowball)>,
+    targets: Query<(Entity, &GlobalTransform, &SnowballTarget)>,
+    wind: Res<Wind>,
+    noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
+    colliders: Res<ColliderGrid>,
+    mut edits: ResMut<TerrainEdits>,
+    mut hits: EventWriter<SnowballHit>,
+    mut commands: Commands
+) {
+    *unstepped += time.delta_secs();
+    let steps = (*unstepped / FLIGHT_STEP) as usize;
+    *unstepped -= steps as f32 * FLIGHT_STEP;
+    let steps = steps.min(MAX_FLIGHT_STEPS);
+
+    for (entity, mut t, mut ball) in query.iter_mut() {
+        let mut hit = None;
+        for _ in 0..steps {
+            integrate(&mut t.translation, &mut ball.velocity, &wind, FLIGHT_STEP);
+            let pos = t.translation;
+
+            // other actors first, they stand on the ground
+            let actor = targets.iter().find(|(_, target_t, target)| {
+                target_t.translation().distance(pos) < target.radius + SNOWBALL_RADIUS
+            });
+
+            hit = if let Some((actor, target_t, _)) = actor {
+                let normal = (pos - target_t.translation()).normalize_or(Vec3::Y);
+                Some(SnowballHit { position: pos, normal, target: HitTarget::Actor(actor) })
+            } else if let Some((prop, normal)) = colliders.hit(pos, SNOWBALL_RADIUS, &config) {
+                // a tree or something
+                Some(SnowballHit { position: pos, normal, target: HitTarget::Prop(prop) })
+            } else {
+                let terrain_h = get_surface_height(pos.x as f64, pos.z as f64, &noise, &config, &edits);
+                (pos.y <= terrain_h).then(|| {
+                    let normal = get_surface_normal(pos.x as f64, pos.z as f64, &noise, &config, &edits);
+                    SnowballHit { position: pos.with_y(terrain_h), normal, target: HitTarget::Terrain }
+                })
+            };
+            if hit.is_some() {
+                break;
+            }
+        }
+
+        if let Some(hit) = hit {
+            // the ground gets a small crater
+            if hit.target == HitTarget::Terrain {
+                edits.stamp(hit.position, Stamp::Crater, &config);
+            }
+            hits.write(hit);
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn spawn_particle(commands: &mut Commands, assets: &SnowballAssets, pos: Vec3, particle: SnowParticle) {
+    commands.spawn((
+        Mesh3d(assets.particle.clone()),
+        MeshMaterial3d(assets.snow.clone()),
+        Transform::from_translation(pos),
+        particle,
+    ));
+}
+
+// a puff of snow flying out along the normal
+fn impact_burst(mut commands: Commands, mut hits: EventReader<SnowballHit>, assets: Res<SnowballAssets>) {
+    let mut rng = thread_rng();
+    for hit in hits.read() {
+        for _ in 0..BURST_PARTICLES {
+            let random = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+            let dir = (hit.normal + random * 0.8).normalize_or(hit.normal);
+            let lifetime = rng.gen_range(0.3..0.7);
+            spawn_particle(&mut commands, &assets, hit.position, SnowParticle {
+                velocity: dir * rng.gen_range(2.0..5.0),
+                gravity: 9.0,
+                life: lifetime,
+                lifetime,
+            });
+        }
+    }
+}
+
+// a white splat lying on the terrain, it fades out over a few seconds
+fn splat_decal(
+    mut commands: Commands,
+    mut hits: EventReader<SnowballHit>,
+    assets: Res<SnowballAssets>,
+    noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
+    edits: Res<TerrainEdits>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut rng = thread_rng();
+    for hit in hits.read().filter(|h| h.target == HitTarget::Terrain) {
+        // the crater is already in the edits, the splat goes to the bottom of it
+        let (x, z) = (hit.position.x as f64, hit.position.z as f64);
+        let ground = hit.position.with_y(get_surface_height(x, z, &noise, &config, &edits));
+        let normal = get_surface_normal(x, z, &noise, &config, &edits);
+
+        // own material, every splat fades on its own
+        let material = materials.add(StandardMaterial {
+            base_color: Color::srgba(0.97, 0.98, 1.0, 0.9),
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: 1.0,
+            depth_bias: 10.0,
+            ..default()
+        });
+        let spin = Quat::from_rotation_z(rng.gen_range(0.0..std::f32::consts::TAU));
+
+        commands.spawn((
+            Mesh3d(assets.splat.clone()),
+            MeshMaterial3d(material),
+            // the circle mesh faces +Z
+            Transform::from_translation(ground + normal * 0.03)
+                .with_rotation(Quat::from_rotation_arc(Vec3::Z, normal) * spin)
+                .with_scale(Vec3::splat(rng.gen_range(0.8..1.3))),
+            SnowSplat { age: 0.0 },
+        ));
+    }
+}
+
+// hitting a tree shakes snow out of the branches
+fn knock_snow_off_trees(mut commands: Commands, mut hits: EventReader<SnowballHit>, assets: Res<SnowballAssets>) {
+    let mut rng = thread_rng();
+    for hit in hits.read() {
+        let HitTarget::Prop(prop) = hit.target else { continue };
+        if prop.kind != PropKind::Tree {
+            continue;
+        }
+        for _ in 0..BURST_PARTICLES * 3 {
+            let offset = Vec3::new(
+                rng.gen_range(-2.0..2.0) * prop.radius,
+                rng.gen_range(0.4..1.0) * prop.height,
+                rng.gen_range(-2.0..2.0) * prop.radius,
+            );
+            let lifetime = rng.gen_range(1.0..2.0);
+            spawn_particle(&mut commands, &assets, prop.base + offset, SnowParticle {
+                velocity: Vec3::new(rng.gen_range(-0.3..0.3), 0.0, rng.gen_range(-0.3..0.3)),
+                gravity: 2.5, // powder drifts down slowly
+                life: lifetime,
+                lifetime,
+            });
+        }
+    }
+}
+
+fn count_score(mut hits: EventReader<SnowballHit>, mut score: ResMut<SnowballScore>) {
+    for hit in hits.read() {
+        let points = match hit.target {
+            HitTarget::Terrain => 0,
+            HitTarget::Prop(_) => 1,
+            HitTarget::Actor(_) => 5,
+        };
+        if points > 0 {
+            score.hits += 1;
+            score.points += points;
+        }
+    }
+}
+
+fn update_particles(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut particles: Query<(Entity, &mut Transform, &mut SnowParticle)>,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut t, mut p) in &mut particles {
+        p.life -= dt;
+        if p.life <= 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        p.velocity.y -= p.gravity * dt;
+        t.translation += p.velocity * dt;
+        t.scale = Vec3::splat(p.life / p.lifetime);
+    }
+}
+
+fn fade_splats(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut splats: Query<(Entity, &mut SnowSplat, &MeshMaterial3d<StandardMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, mut splat, material) in &mut splats {
+        splat.age += time.delta_secs();
+        if splat.age >= DECAL_LIFETIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        // stays for a while, then fades in the last third
+        let fade = ((DECAL_LIFETIME - splat.age) / (DECAL_LIFETIME / 3.0)).min(1.0);
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.base_color.set_alpha(0.9 * fade);
+        }
+    }
+}
@@ -1,125 +1,359 @@
+use std::collections::HashMap;
 use bevy::prelude::*;
+use bevy::asset::RenderAssetUsages;
+use bevy::render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues};
+use bevy::render::view::NoFrustumCulling;
+use bevy::pbr::NotShadowCaster;
 use rand::{Rng, thread_rng};
 use std::f32::consts::TAU;
-use bevy::pbr::NotShadowCaster;
-
-const SNOW_RADIUS: f32 = 40.0;
-const SNOW_PER_SECOND: f32 = 2400.0;
-const SPAWN_HEIGHT: f32 = 2.0;
 
 use crate::noise::NoiseGenerators;
 use crate::chunks::get_height;
+use crate::config::{TerrainChanged, WorldGenConfig};
+use crate::deform::TerrainEdits;
+use crate::asset_paths::AssetPaths;
+use crate::world_gen::{ChunkCoord, LoadedChunks};
+use crate::sky::SkyLight;
+use crate::weather::Weather;
+use crate::wind::Wind;
+
+// snowfall as a pool of up to snow_max_flakes flakes: landed flakes get swapped out of the live range
+// and new ones fill it up again, nothing gets spawned or despawned. All live flakes are quads in one mesh,
+// so it's one draw call, and the mesh only has as many quads as there are flakes falling right now
+// the ground comes from a coarse heightfield per loaded chunk instead of noise per flake,
+// footprints, tracks and craters go on top so the flakes land in them
+
+const GROUND_CELLS: usize = 16; // heightfield cells per chunk side
+const GROUND_CHUNKS_PER_FRAME: usize = 4; // how many chunks get a heightfield per frame at most
+const FLAKE_BRIGHTNESS: f32 = 10.0; // unlit and over 1 so the bloom catches them, at noon
 
 pub struct SnowflakePlugin;
 
 impl Plugin for SnowflakePlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<SnowflakeAssets>()
-           .add_systems(Startup, setup_assets)
-           .add_systems(Update, (spawn_snowflakes, update_snowflakes));
+        app.init_resource::<AssetPaths>()
+           .init_resource::<SnowGround>()
+           .insert_resource(SnowPool::new(0))
+           .add_systems(Startup, setup_snowfall)
+           .add_systems(Update, ((resize_snow_pool, update_snow_ground, update_snowfall).chain(), dim_snowflakes));
     }
 }
 
-#[derive(Component)]
-struct Snowflake {
+#[derive(Clone, Copy, Default)]
+struct Flake {
+    position: Vec3,
     velocity: Vec3,
-    rotation_speed: Vec3,
+    spin: f32, // rotation in the camera plane
+    spin_speed: f32,
+}
+
+/// The falling flakes, at most snow_max_flakes from the config.
+#[derive(Resource)]
+pub struct SnowPool {
+    flakes: Vec<Flake>, // only the live ones, landed flakes get swap-removed
+    capacity: usize,
+    to_spawn: f32, // fractional flakes left over from the last frame
 }
 
+impl SnowPool {
+    fn new(capacity: usize) -> Self {
+        Self { flakes: Vec::with_capacity(capacity), capacity, to_spawn: 0.0 }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn alive(&self) -> usize {
+        self.flakes.len()
+    }
+}
+
+/// Coarse noise heights of the loaded chunks, only used to tell when a flake landed.
+/// The edits change all the time, `height_at` adds them when asked instead of rebuilding chunks.
 #[derive(Resource, Default)]
-struct SnowflakeAssets {
-    mesh: Handle<Mesh>,
-    material: Handle<StandardMaterial>,
+pub struct SnowGround {
+    chunks: HashMap<ChunkCoord, Vec<f32>>, // (GROUND_CELLS + 1)² heights
 }
 
-fn setup_assets(
-    mut assets: ResMut<SnowflakeAssets>,
+impl SnowGround {
+    fn build_chunk(coord: ChunkCoord, noise: &NoiseGenerators, config: &WorldGenConfig) -> Vec<f32> {
+        let size = config.chunk_world_size();
+        let cell = size / GROUND_CELLS as f32;
+        let mut heights = Vec::with_capacity((GROUND_CELLS + 1) * (GROUND_CELLS + 1));
+        for z in 0..=GROUND_CELLS {
+            for x in 0..=GROUND_CELLS {
+                let wx = coord.x as f32 * size + x as f32 * cell;
+                let wz = coord.z as f32 * size + z as f32 * cell;
+                heights.push(get_height(wx as f64, wz as f64, noise, config));
+            }
+        }
+        heights
+    }
+
+    // bilinear between the cell corners plus the edits, None over chunks that aren't loaded (yet)
+    pub fn height_at(&self, pos: Vec3, config: &WorldGenConfig, edits: &TerrainEdits) -> Option<f32> {
+        let coord = config.chunk_coord(pos);
+        let heights = self.chunks.get(&coord)?;
+        let size = config.chunk_world_size();
+        let local = (pos.xz() - Vec2::new(coord.x as f32, coord.z as f32) * size) / size * GROUND_CELLS as f32;
+        let x = (local.x.floor() as usize).min(GROUND_CELLS - 1);
+        let z = (local.y.floor() as usize).min(GROUND_CELLS - 1);
+        let (fx, fz) = (local.x - x as f32, local.y - z as f32);
+
+        let h = |x: usize, z: usize| heights[z * (GROUND_CELLS + 1) + x];
+        let top = h(x, z).lerp(h(x + 1, z), fx);
+        let bottom = h(x, z + 1).lerp(h(x + 1, z + 1), fx);
+        Some(top.lerp(bottom, fz) + edits.offset_at(pos.x as f64, pos.z as f64, config))
+    }
+}
+
+#[derive(Component)]
+struct SnowfallMesh(Handle<Mesh>);
+
+fn snowfall_mesh() -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default());
+    write_quads(&mut mesh, &[], Vec3::X, Vec3::Y);
+    mesh
+}
+
+// one camera facing quad per live flake. Without flakes there's a single one squashed to a point,
+// so the mesh never ends up without vertices
+fn write_quads(mesh: &mut Mesh, flakes: &[Flake], right: Vec3, up: Vec3) {
+    let quads = flakes.len().max(1);
+    let half = 0.1;
+
+    let mut positions = match mesh.remove_attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(positions)) => positions,
+        _ => Vec::new(),
+    };
+    positions.clear();
+    for flake in flakes {
+        let (sin, cos) = flake.spin.sin_cos();
+        let r = (right * cos + up * sin) * half;
+        let u = (up * cos - right * sin) * half;
+        let p = flake.position;
+        positions.extend([(p - r - u).to_array(), (p + r - u).to_array(), (p + r + u).to_array(), (p - r + u).to_array()]);
+    }
+    positions.resize(quads * 4, [0.0; 3]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+
+    // the rest is the same for every quad, it only changes when the number of flakes does
+    if mesh.indices().is_some_and(|indices| indices.len() == quads * 6) {
+        return;
+    }
+    let mut uvs = Vec::with_capacity(quads * 4);
+    let mut indices = Vec::with_capacity(quads * 6);
+    for i in 0..quads as u32 {
+        uvs.extend([[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]);
+        let v = i * 4;
+        indices.extend([v, v + 1, v + 2, v, v + 2, v + 3]);
+    }
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0f32, 0.0, 1.0]; quads * 4]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_indices(Indices::U32(indices));
+}
+
+fn setup_snowfall(
+    mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     asset_server: Res<AssetServer>,
+    paths: Res<AssetPaths>,
 ) {
-    assets.mesh = meshes.add(Rectangle::new(0.2, 0.2));
-    assets.material = materials.add(StandardMaterial {
-        base_color_texture: Some(asset_server.load("snowflake.png")),
-        base_color: Color::linear_rgb(10.0, 10.0, 10.0),
+    let mesh = meshes.add(snowfall_mesh());
+
+    let material = materials.add(StandardMaterial {
+        base_color_texture: Some(asset_server.load(&paths.snowflake)),
+        base_color: Color::linear_rgb(FLAKE_BRIGHTNESS, FLAKE_BRIGHTNESS, FLAKE_BRIGHTNESS),
         alpha_mode: AlphaMode::Blend,
         unlit: true,
         double_sided: true,
+        cull_mode: None,
         ..default()
+    });
+
+    commands.spawn((
+        Mesh3d(mesh.clone()),
+        MeshMaterial3d(material),
+        SnowfallMesh(mesh),
+        Transform::default(),
+        NotShadowCaster,
+        // the flakes move with the camera, the bounding box of the first frame would be wrong
+        NoFrustumCulling,
+    ));
+}
+
+// the config file comes in after Startup and can be hot reloaded, so the pool follows snow_max_flakes.
+// a smaller pool drops the flakes that don't fit anymore
+fn resize_snow_pool(config: Res<WorldGenConfig>, mut pool: ResMut<SnowPool>) {
+    if pool.capacity == config.snow_max_flakes {
+        return;
     }
-    );
+    pool.capacity = config.snow_max_flakes;
+    pool.flakes.truncate(config.snow_max_flakes);
+    let missing = config.snow_max_flakes - pool.flakes.len();
+    pool.flakes.reserve_exact(missing); // no reallocation while it snows
 }
 
-fn spawn_snowflakes(
-    mut commands: Commands,
-    assets: Res<SnowflakeAssets>,
+// builds the heightfield of newly loaded chunks (a few per frame, nearest first) and drops the unloaded ones
+fn update_snow_ground(
+    mut ground: ResMut<SnowGround>,
+    loaded: Res<LoadedChunks>,
+    noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
+    mut terrain_changed: EventReader<TerrainChanged>,
     camera_query: Query<&GlobalTransform, With<Camera3d>>,
+) {
+    if terrain_changed.read().count() > 0 || noise.is_changed() {
+        ground.chunks.clear();
+    }
+    ground.chunks.retain(|coord, _| loaded.chunks.contains_key(coord));
+
+    // the flakes around the camera need their ground first, after a load or a teleport too
+    let center = camera_query.single().map(|t| config.chunk_coord(t.translation())).unwrap_or(ChunkCoord { x: 0, z: 0 });
+    let mut missing: Vec<ChunkCoord> = loaded.chunks.keys()
+        .filter(|coord| !ground.chunks.contains_key(coord))
+        .copied()
+        .collect();
+    missing.sort_by_key(|c| (c.x - center.x).pow(2) + (c.z - center.z).pow(2));
+    for coord in missing.into_iter().take(GROUND_CHUNKS_PER_FRAME) {
+        ground.chunks.insert(coord, SnowGround::build_chunk(coord, &noise, &config));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_snowfall(
     time: Res<Time>,
+    config: Res<WorldGenConfig>,
+    wind: Res<Wind>,
+    weather: Res<Weather>,
+    ground: Res<SnowGround>,
+    edits: Res<TerrainEdits>,
+    mut pool: ResMut<SnowPool>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    snow_query: Query<&SnowfallMesh>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
 ) {
     let Ok(cam_transform) = camera_query.single() else { return };
     let cam_pos = cam_transform.translation();
+    let dt = time.delta_secs();
     let mut rng = thread_rng();
+    let pool = &mut *pool;
 
-    // spawn every second
-    let dt = time.delta_secs();
-    let to_spawn = (SNOW_PER_SECOND * dt).round() as usize;
+    // flakes drift with the wind while they fall, so they start a bit upwind
+    let upwind = (-wind.at(cam_pos) * 3.0).clamp_length_max(config.snow_radius * 0.5);
+    let center = cam_pos + upwind;
+
+    // new flakes at the emission rate, as long as the pool isn't full
+    pool.to_spawn += config.snow_per_second * weather.params.snow * dt;
+    while pool.to_spawn >= 1.0 {
+        if pool.flakes.len() >= pool.capacity {
+            pool.to_spawn = 0.0;
+            break;
+        }
+        pool.to_spawn -= 1.0;
 
-    for _ in 0..to_spawn {
         let angle = rng.gen_range(0.0..TAU);
-        let radius = SNOW_RADIUS * rng.gen_range(0.0..1.0f32).sqrt(); // größe
-        
-        let x = cam_pos.x + angle.cos() * radius;
-        let z = cam_pos.z + angle.sin() * radius;
-        let y = cam_pos.y + SPAWN_HEIGHT + rng.gen_range(2.0..17.0);
-
-        commands.spawn((
-            Mesh3d(assets.mesh.clone()),
-            MeshMaterial3d(assets.material.clone()),
-            NotShadowCaster,
-            Transform::from_xyz(x, y, z),
-            Snowflake {
-                velocity: Vec3::new(0.0, -rng.gen_range(2.0..4.0), 0.0),
-                rotation_speed: Vec3::new(
-                    rng.gen_range(-2.0..2.0),
-                    rng.gen_range(-2.0..2.0),
-                    rng.gen_range(-2.0..2.0),
-                ),
-            },
-        ));
+        let radius = config.snow_radius * rng.gen_range(0.0..1.0f32).sqrt(); // größe
+        pool.flakes.push(Flake {
+            position: Vec3::new(
+                center.x + angle.cos() * radius,
+                cam_pos.y + config.snow_spawn_height + rng.gen_range(2.0..17.0),
+                center.z + angle.sin() * radius,
+            ),
+            velocity: Vec3::new(0.0, -rng.gen_range(2.0..4.0), 0.0),
+            spin: rng.gen_range(0.0..TAU),
+            spin_speed: rng.gen_range(-2.0..2.0),
+        });
+    }
+
+    let despawn_dist_sq = (config.snow_radius * 1.2 + upwind.length()).powi(2);
+    let lowest = cam_pos.y - config.snow_radius; // fell through where there is no heightfield yet
+    let mut i = 0;
+    while i < pool.flakes.len() {
+        let flake = &mut pool.flakes[i];
+        flake.position += (flake.velocity + wind.at(flake.position)) * dt;
+        flake.spin += flake.spin_speed * dt;
+
+        // landed, out of reach of the player or way too low: out of the live range
+        let landed = ground.height_at(flake.position, &config, &edits).is_some_and(|h| flake.position.y <= h);
+        if landed
+            || flake.position.y < lowest
+            || flake.position.xz().distance_squared(cam_pos.xz()) > despawn_dist_sq
+        {
+            pool.flakes.swap_remove(i); // the last one moves here and gets updated next
+        } else {
+            i += 1;
+        }
     }
+
+    let Ok(snow) = snow_query.single() else { return };
+    let Some(mesh) = meshes.get_mut(&snow.0) else { return };
+    write_quads(mesh, &pool.flakes, *cam_transform.right(), *cam_transform.up());
 }
 
-fn update_snowflakes(
-    mut commands: Commands,
-    time: Res<Time>,
-    camera_query: Query<&GlobalTransform, With<Camera3d>>,
-    mut query: Query<(Entity, &mut Transform, &Snowflake)>,
-    noise: Res<NoiseGenerators>,
+// glowing flakes look wrong at night. Only touches the material when it's visibly off,
+// every change uploads it again
+fn dim_snowflakes(
+    sky_light: Res<SkyLight>,
+    snow_query: Query<&MeshMaterial3d<StandardMaterial>, With<SnowfallMesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    let dt = time.delta_secs();
-    let cam_pos = camera_query.single().map(|t| t.translation()).unwrap_or(Vec3::ZERO);
-    let despawn_dist_sq = (SNOW_RADIUS * 1.2).powi(2);
-
-    for (entity, mut transform, snowflake) in &mut query {
-        transform.translation += snowflake.velocity * dt;
-        transform.rotate_local_x(snowflake.rotation_speed.x * dt);
-        transform.rotate_local_y(snowflake.rotation_speed.y * dt);
-        transform.rotate_local_z(snowflake.rotation_speed.z * dt);
-
-        // höhe von terrain ausrechnen
-        let terrain_h = get_height(
-            transform.translation.x as f64,
-            transform.translation.z as f64,
-            &noise,
-        );
-        // despawnen when unter der ausgerechneten höhe
-        if transform.translation.y <= terrain_h {
-            commands.entity(entity).despawn();
-            continue;
-        }
-        // despawnen, wenn aus reichweite von Player
-        if transform.translation.xz().distance_squared(cam_pos.xz()) > despawn_dist_sq {
-            commands.entity(entity).despawn();
+    let Ok(material) = snow_query.single() else { return };
+    let brightness = FLAKE_BRIGHTNESS * sky_light.snow;
+    let current = materials.get(&material.0).map(|m| m.base_color.to_linear().red);
+    if current.is_some_and(|c| (c - brightness).abs() > 0.05)
+        && let Some(m) = materials.get_mut(&material.0)
+    {
+        m.base_color = Color::linear_rgb(brightness, brightness, brightness);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunks::get_surface_height;
+    use crate::deform::Stamp;
+
+    #[test]
+    fn heightfield_matches_the_terrain_at_the_corners() {
+        let noise = NoiseGenerators::new(3);
+        let config = WorldGenConfig::default();
+        let coord = ChunkCoord { x: -1, z: 2 };
+        let ground = SnowGround { chunks: HashMap::from([(coord, SnowGround::build_chunk(coord, &noise, &config))]) };
+
+        let cell = config.chunk_world_size() / GROUND_CELLS as f32;
+        let origin = Vec2::new(coord.x as f32, coord.z as f32) * config.chunk_world_size();
+        // a crater at one of the corners, the flakes have to land in it
+        let mut edits = TerrainEdits::default();
+        let crater = origin + Vec2::new(3.0, 7.0) * cell;
+        edits.stamp(Vec3::new(crater.x, 0.0, crater.y), Stamp::Crater, &config);
+
+        for (x, z) in [(0, 0), (3, 7), (15, 15)] {
+            let pos = origin + Vec2::new(x as f32, z as f32) * cell;
+            let expected = get_surface_height(pos.x as f64, pos.y as f64, &noise, &config, &edits);
+            let cached = ground.height_at(Vec3::new(pos.x, 0.0, pos.y), &config, &edits).unwrap();
+            assert!((cached - expected).abs() < 1e-3, "{cached} != {expected}");
         }
+        assert!(edits.offset_at(crater.x as f64, crater.y as f64, &config) < -0.1);
+        assert!(ground.height_at(Vec3::ZERO, &config, &edits).is_none());
+    }
+
+    #[test]
+    fn mesh_only_holds_the_live_flakes() {
+        let mut mesh = snowfall_mesh();
+        assert_eq!(mesh.count_vertices(), 4);
+
+        let flakes = vec![Flake { position: Vec3::new(1.0, 2.0, 3.0), ..default() }; 3];
+        write_quads(&mut mesh, &flakes, Vec3::X, Vec3::Y);
+        assert_eq!(mesh.count_vertices(), 12);
+        assert_eq!(mesh.indices().map(|i| i.len()), Some(18));
+
+        write_quads(&mut mesh, &flakes[..1], Vec3::X, Vec3::Y);
+        assert_eq!(mesh.count_vertices(), 4);
+        assert_eq!(mesh.indices().map(|i| i.len()), Some(6));
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else { panic!() };
+        assert!(positions.iter().all(|p| Vec3::from_array(*p).distance(Vec3::new(1.0, 2.0, 3.0)) < 0.2));
     }
 }
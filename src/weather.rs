@@ -0,0 +1,223 @@
+use bevy::prelude::*;
+use rand::{Rng, thread_rng};
+
+use crate::biome::{Biome, BiomeMap};
+use crate::config::WorldGenConfig;
+use crate::input::{Action, ActionState};
+use crate::noise::NoiseGenerators;
+use crate::player::FlyCamera;
+
+// weather: every few minutes the sky picks a new state, mountains and tundra get more blizzards.
+// the numbers of the state (snow, fog, sky, sun, wind) blend over slowly, everything else reads Weather::params.
+// the sky module mixes the colours and the sun with the time of day.
+// F6 forces a state for testing, pressing it through all of them goes back to automatic
+
+const TRANSITION_TIME: f32 = 20.0; // seconds until a new state is mostly there
+const MIN_DURATION: f32 = 90.0;
+const MAX_DURATION: f32 = 240.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WeatherState {
+    Clear,
+    Flurries,
+    HeavySnow,
+    Blizzard,
+}
+
+impl WeatherState {
+    pub const ALL: [WeatherState; 4] = [
+        WeatherState::Clear,
+        WeatherState::Flurries,
+        WeatherState::HeavySnow,
+        WeatherState::Blizzard,
+    ];
+
+    pub fn params(self) -> WeatherParams {
+        match self {
+            WeatherState::Clear => WeatherParams {
+                snow: 0.0,
+                fog_distance: 1.6,
+                fog_color: Vec3::new(0.8, 0.9, 1.0),
+                sky_color: Vec3::new(0.68, 0.85, 0.9),
+                sun: 1.0,
+                wind: 0.5,
+                gusts: 0.3,
+            },
+            WeatherState::Flurries => WeatherParams {
+                snow: 0.35,
+                fog_distance: 1.1,
+                fog_color: Vec3::new(0.8, 0.87, 0.95),
+                sky_color: Vec3::new(0.7, 0.78, 0.86),
+                sun: 0.75,
+                wind: 0.8,
+                gusts: 0.6,
+            },
+            WeatherState::HeavySnow => WeatherParams {
+                snow: 1.0,
+                fog_distance: 0.5,
+                fog_color: Vec3::new(0.82, 0.86, 0.9),
+                sky_color: Vec3::new(0.76, 0.79, 0.83),
+                sun: 0.45,
+                wind: 1.2,
+                gusts: 1.0,
+            },
+            WeatherState::Blizzard => WeatherParams {
+                snow: 2.0,
+                fog_distance: 0.12,
+                fog_color: Vec3::new(0.88, 0.9, 0.93),
+                sky_color: Vec3::new(0.85, 0.87, 0.9),
+                sun: 0.2,
+                wind: 3.0,
+                gusts: 1.6,
+            },
+        }
+    }
+
+    // how likely each state is to come next in a biome
+    fn chance(self, biome: Biome) -> f32 {
+        match (self, biome) {
+            (WeatherState::Blizzard, Biome::GlacialMountains | Biome::Tundra) => 3.0,
+            (WeatherState::Blizzard, Biome::Forest) => 0.5,
+            (WeatherState::Clear, Biome::FrozenLake | Biome::Plains) => 3.0,
+            (WeatherState::Flurries, Biome::Forest) => 3.0,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Multipliers and colours of one weather state, `Weather::params` blends between them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WeatherParams {
+    pub snow: f32, // times snow_per_second
+    pub fog_distance: f32, // times the normal fog distance
+    pub fog_color: Vec3, // srgb
+    pub sky_color: Vec3,
+    pub sun: f32, // times the sun illuminance
+    pub wind: f32, // times wind_strength
+    pub gusts: f32, // times wind_gustiness
+}
+
+impl WeatherParams {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            snow: self.snow.lerp(other.snow, t),
+            fog_distance: self.fog_distance.lerp(other.fog_distance, t),
+            fog_color: self.fog_color.lerp(other.fog_color, t),
+            sky_color: self.sky_color.lerp(other.sky_color, t),
+            sun: self.sun.lerp(other.sun, t),
+            wind: self.wind.lerp(other.wind, t),
+            gusts: self.gusts.lerp(other.gusts, t),
+        }
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct Weather {
+    pub state: WeatherState, // where the weather is going
+    pub params: WeatherParams, // what it is right now
+    pub forced: bool, // F6 picked the state, the timer does nothing
+    pub next_change: f32, // seconds
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            state: WeatherState::Flurries,
+            params: WeatherState::Flurries.params(),
+            forced: false,
+            next_change: MIN_DURATION,
+        }
+    }
+}
+
+impl Weather {
+    /// Jumps to a state right away, for tests and the debug key.
+    pub fn force(&mut self, state: WeatherState) {
+        self.state = state;
+        self.params = state.params();
+        self.forced = true;
+    }
+}
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Weather>()
+            .add_systems(PreUpdate, update_weather)
+            .add_systems(Update, force_weather);
+    }
+}
+
+pub fn update_weather(
+    time: Res<Time>,
+    noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
+    player_query: Query<&Transform, With<FlyCamera>>,
+    mut weather: ResMut<Weather>,
+) {
+    let dt = time.delta_secs();
+    let target = weather.state.params();
+    weather.params = weather.params.lerp(target, 1.0 - (-dt * 3.0 / TRANSITION_TIME).exp());
+
+    if weather.forced {
+        return;
+    }
+    weather.next_change -= dt;
+    if weather.next_change > 0.0 {
+        return;
+    }
+
+    // the biome at the player decides what's likely
+    let biome = player_query.single()
+        .map(|p| BiomeMap::new(&noise, &config).biome_at(p.translation.x as f64, p.translation.z as f64))
+        .unwrap_or(Biome::Plains);
+    let mut rng = thread_rng();
+    let total: f32 = WeatherState::ALL.iter().map(|s| s.chance(biome)).sum();
+    let mut roll = rng.gen_range(0.0..total);
+    for state in WeatherState::ALL {
+        roll -= state.chance(biome);
+        if roll <= 0.0 {
+            weather.state = state;
+            break;
+        }
+    }
+    weather.next_change = rng.gen_range(MIN_DURATION..MAX_DURATION);
+    info!("weather: {:?}", weather.state);
+}
+
+// F6: clear, flurries, heavy snow, blizzard, automatic again
+fn force_weather(actions: Res<ActionState>, mut weather: ResMut<Weather>) {
+    if !actions.just_pressed(Action::ForceWeather) {
+        return;
+    }
+    let next = match (weather.forced, weather.state) {
+        (false, _) => Some(WeatherState::Clear),
+        (true, WeatherState::Blizzard) => None,
+        (true, state) => WeatherState::ALL.into_iter().skip_while(|s| *s != state).nth(1),
+    };
+    match next {
+        Some(state) => weather.force(state),
+        None => {
+            weather.forced = false;
+            weather.next_change = 0.0;
+        }
+    }
+    info!("weather: {:?}{}", weather.state, if weather.forced { " (forced)" } else { "" });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params_blend_between_states() {
+        let clear = WeatherState::Clear.params();
+        let blizzard = WeatherState::Blizzard.params();
+        assert_eq!(clear.lerp(blizzard, 0.0), clear);
+        assert!((clear.lerp(blizzard, 1.0).sun - blizzard.sun).abs() < 1e-6);
+        let half = clear.lerp(blizzard, 0.5);
+        assert!(half.snow > clear.snow && half.snow < blizzard.snow);
+        assert!(half.fog_distance < clear.fog_distance && half.fog_distance > blizzard.fog_distance);
+    }
+}
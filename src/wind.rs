@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use noise::NoiseFn;
+
+use crate::biome::{Biome, BiomeMap};
+use crate::config::WorldGenConfig;
+use crate::noise::NoiseGenerators;
+use crate::player::FlyCamera;
+use crate::weather::{update_weather, Weather};
+
+// one wind for the whole world. Direction and strength wander slowly, gusts come from noise over time
+// and roll through the world as fronts. Snow, snowballs and the sled all ask Wind::at
+
+const GUST_FRONT_SPACING: f32 = 40.0; // metres between gust fronts
+const GUST_FRONT_SPEED: f32 = 2.0; // radians per second of the front wave
+
+/// The air right now. `at` is cheap enough to call for every snowflake.
+#[derive(Resource, Clone, Debug)]
+pub struct Wind {
+    pub direction: Vec2, // where the wind blows to (x, z), length 1
+    pub strength: f32, // metres per second, without gusts
+    pub gust: f32, // 0..1, how hard the gusts are right now
+    pub exposure: f32, // biome around the player, the forest shelters, open tundra doesn't
+    time: f32,
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Self { direction: Vec2::X, strength: 0.0, gust: 0.0, exposure: 1.0, time: 0.0 }
+    }
+}
+
+impl Wind {
+    /// Wind velocity at a world position, stronger higher up and in the gust fronts.
+    pub fn at(&self, pos: Vec3) -> Vec3 {
+        let along = pos.xz().dot(self.direction) / GUST_FRONT_SPACING;
+        let front = (along - self.time * GUST_FRONT_SPEED).sin() * 0.5 + 0.5;
+        let height = 1.0 + (pos.y.max(0.0) / 60.0).min(1.0) * 0.5;
+        let speed = self.strength * self.exposure * height * (1.0 + self.gust * front);
+        Vec3::new(self.direction.x, 0.0, self.direction.y) * speed
+    }
+
+    // compass direction the wind comes from, for the HUD
+    pub fn from_compass(&self) -> &'static str {
+        const NAMES: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+        // -z is north, +x is east
+        let from = -self.direction;
+        let angle = from.x.atan2(-from.y).rem_euclid(std::f32::consts::TAU);
+        NAMES[((angle / std::f32::consts::FRAC_PI_4).round() as usize) % 8]
+    }
+}
+
+pub struct WindPlugin;
+
+impl Plugin for WindPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Wind>()
+            // before everything that gets blown around
+            .add_systems(PreUpdate, update_wind.after(update_weather));
+    }
+}
+
+fn update_wind(
+    time: Res<Time>,
+    noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
+    weather: Res<Weather>,
+    player_query: Query<&Transform, With<FlyCamera>>,
+    mut wind: ResMut<Wind>,
+) {
+    wind.time += time.delta_secs();
+    let t = wind.time as f64;
+
+    // the direction wanders around the configured one, the strength breathes a bit
+    let wander = noise.wind.get([t * 0.01, 0.5]) as f32 * 0.8;
+    let angle = config.wind_direction.to_radians() + wander;
+    wind.direction = Vec2::new(angle.sin(), -angle.cos()); // 0° blows north
+    wind.strength = config.wind_strength * weather.params.wind * (0.8 + 0.2 * noise.wind.get([t * 0.03, 7.5]) as f32);
+    wind.gust = (noise.wind.get([t * 0.3, 13.5]) as f32 * 0.5 + 0.5) * config.wind_gustiness * weather.params.gusts;
+
+    // one biome lookup per frame, at the player
+    if let Ok(player) = player_query.single() {
+        let weights = BiomeMap::new(&noise, &config).weights(player.translation.x as f64, player.translation.z as f64);
+        wind.exposure = weights.blend(Biome::wind_exposure);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compass_names_where_the_wind_comes_from() {
+        // blowing to the south (+z) means it comes from the north
+        let wind = Wind { direction: Vec2::Y, ..default() };
+        assert_eq!(wind.from_compass(), "N");
+        let wind = Wind { direction: Vec2::NEG_X, ..default() };
+        assert_eq!(wind.from_compass(), "E");
+    }
+}
@@ -1,37 +1,85 @@
 use bevy::prelude::*;
+#[cfg(feature = "debug-wireframe")]
 use bevy::pbr::wireframe::{WireframeConfig, WireframePlugin};
+#[cfg(feature = "debug-wireframe")]
+use crate::input::{Action, ActionState};
 
 pub struct WorldPlugin;
 
+pub const SUN_ILLUMINANCE: f32 = 10000.0; // clear sky, the weather turns it down
+pub const MOON_ILLUMINANCE: f32 = 400.0;
+
+#[derive(Component)]
+pub struct Sun;
+
+#[derive(Component)]
+pub struct Moon;
+
+/// In game clock, 0..24. The sky module moves it, the save keeps it.
+#[derive(Resource)]
+pub struct TimeOfDay {
+    pub hours: f32,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self { hours: 12.0 }
+    }
+}
+
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<TimeOfDay>();
         app.add_systems(Startup, setup_lighting); // , spawn_ground
-        // Wireframes
-        app.add_systems(Update, toggle_wireframe);
-        app.add_plugins(WireframePlugin::default());
         // spawn background
         app.insert_resource(ClearColor(Color::srgb_u8(173, 216, 230)));
     }
 }
 
+/// F3 wireframe view, needs the renderer.
+#[cfg(feature = "debug-wireframe")]
+pub struct WireframeDebugPlugin;
+
+#[cfg(feature = "debug-wireframe")]
+impl Plugin for WireframeDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(WireframePlugin::default())
+            .add_systems(Update, toggle_wireframe);
+    }
+}
+
+// the sky moves both of them around
 fn setup_lighting(mut commands: Commands) {
     commands.spawn((
+        Sun,
         DirectionalLight {
-            illuminance: 10000.0,
+            illuminance: SUN_ILLUMINANCE,
             shadows_enabled: true,
             ..default()
         },
         Transform::from_xyz(40.0, 80.0, 40.0)
             .looking_at(Vec3::ZERO, Vec3::Y),
     ));
+    // bluish and without shadows, one shadow map is enough
+    commands.spawn((
+        Moon,
+        DirectionalLight {
+            illuminance: 0.0,
+            color: Color::srgb(0.6, 0.7, 1.0),
+            shadows_enabled: false,
+            ..default()
+        },
+        Transform::default(),
+    ));
 }
 
+#[cfg(feature = "debug-wireframe")]
 fn toggle_wireframe(
     mut wireframe_config: ResMut<WireframeConfig>,
-    keyboard: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
 ) {
     // Mit F3 Taste togglen
-    if keyboard.just_pressed(KeyCode::F3) {
+    if actions.just_pressed(Action::ToggleWireframe) {
         wireframe_config.global = !wireframe_config.global;
         println!("Wireframe: {}", wireframe_config.global);
     }
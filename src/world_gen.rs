@@ -1,15 +1,20 @@
 use std::collections::HashMap; // for saving chunks
 use bevy::prelude::*;
-use rand::Rng;
+use bevy::tasks::{block_on, AsyncComputeTaskPool, Task};
 
 use crate::chunks::*;
+use crate::biome::PropKind;
+#[cfg(feature = "props")]
+use crate::props::*;
+use crate::collision::{prop_collider, ColliderGrid, PropCollider};
+use crate::deform::TerrainEdits;
+use crate::asset_paths::AssetPaths;
 use crate::noise::NoiseGenerators;
+use crate::config::{TerrainChanged, WorldGenConfig};
+use crate::player::FlyCamera;
 
-pub const CHUNK_SIZE: usize = 32;
-pub const VERTEX_SPACING: f32 = 3.0; // wie viele verticies in einem chunk sind
-pub const RENDER_DISTANCE: i32 = 10;
-pub const NOISE_FREQ: f64 = 0.01; // wie hart die übergänge sind
-pub const NOISE_AMP: f32 = 10.0; // wie steil alles ist, also berge und so
+pub const MAX_CHUNKS_PER_FRAME: usize = 4; // how many finished chunks get spawned per frame
+pub const LOD_RINGS: [i32; 3] = [2, 5, 8]; // chunk distance up to which lod 0, 1, 2 is used, after that lod 3
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct ChunkCoord {
@@ -17,9 +22,42 @@ pub struct ChunkCoord {
     pub z: i32,
 }
 
+pub struct LoadedChunk {
+    pub entity: Entity,
+    pub lod: u32,
+    pub mesh: Handle<Mesh>, // terrain mesh, swapped out when the snow gets deformed
+}
+
 #[derive(Resource, Default)]
 pub struct LoadedChunks {
-    pub chunks: HashMap<ChunkCoord, Entity>,
+    pub chunks: HashMap<ChunkCoord, LoadedChunk>,
+}
+
+pub struct PendingChunk {
+    pub lod: u32,
+    pub task: Task<ChunkData>,
+}
+
+// chunks that are still being generated on the AsyncComputeTaskPool
+#[derive(Resource, Default)]
+pub struct PendingChunks {
+    pub tasks: HashMap<ChunkCoord, PendingChunk>,
+}
+
+// terrain meshes being rebuilt after a deformation, props and ice stay as they are
+#[derive(Resource, Default)]
+pub struct PendingRemeshes {
+    pub tasks: HashMap<ChunkCoord, PendingRemesh>,
+}
+
+pub struct PendingRemesh {
+    pub lod: u32,
+    pub task: Task<Mesh>,
+}
+
+// lod ring for a chunk, based on its distance (in chunks) to the player chunk
+pub fn lod_for_distance(dist: i32) -> u32 {
+    LOD_RINGS.iter().position(|&ring| dist <= ring).unwrap_or(LOD_RINGS.len()) as u32
 }
 
 pub struct WorldGenPlugin;
@@ -28,100 +66,104 @@ impl Plugin for WorldGenPlugin {
     fn build(&self, app: &mut App) {
         app
             .init_resource::<LoadedChunks>()
-            .add_systems(Startup, load_tree_model)
-            .add_systems(Startup, load_candy_cane)
-            .add_systems(Update, chunk_system);
+            .init_resource::<PendingChunks>()
+            .init_resource::<ColliderGrid>()
+            .init_resource::<TerrainEdits>()
+            .init_resource::<PendingRemeshes>()
+            .init_resource::<AssetPaths>()
+            .add_systems(Startup, setup_terrain_materials)
+            .add_systems(Update, (
+                regenerate_chunks,
+                // chunks get their props merged in the task, so wait for the models
+                chunk_system.run_if(props_ready),
+                spawn_finished_chunks,
+                remesh_deformed_chunks,
+                apply_finished_remeshes,
+            ).chain());
+
+        // headless runs bring their own (empty) PropModels, there's no gltf loader without a renderer
+        #[cfg(feature = "props")]
+        app.add_systems(Startup, load_prop_gltfs.run_if(not(resource_exists::<PropModels>)))
+            .add_systems(Update, extract_prop_models.run_if(not(resource_exists::<PropModels>)));
     }
 }
 
+#[cfg(feature = "props")]
+fn props_ready(models: Option<Res<PropModels>>) -> bool {
+    models.is_some()
+}
+
+#[cfg(not(feature = "props"))]
+fn props_ready() -> bool {
+    true
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn chunk_system(
     mut commands: Commands,
     mut loaded: ResMut<LoadedChunks>,
-    player_query: Query<&GlobalTransform, With<Camera3d>>,
-    tree_model: Res<TreeModel>, 
-    candy_cane: Res<CandyCane>,
+    mut pending: ResMut<PendingChunks>,
+    player_query: Query<&GlobalTransform, With<FlyCamera>>,
     noise: Res<NoiseGenerators>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<WorldGenConfig>,
+    #[cfg(feature = "props")] prop_models: Res<PropModels>,
+    edits: Res<TerrainEdits>,
+    mut colliders: ResMut<ColliderGrid>,
 ) {
     let Ok(player_transform) = player_query.single() else { return };
-    let player_pos = player_transform.translation();
-
-    let cx = (player_pos.x / (CHUNK_SIZE as f32 * VERTEX_SPACING)).floor() as i32; // chunk coords 
-    let cz = (player_pos.z / (CHUNK_SIZE as f32 * VERTEX_SPACING)).floor() as i32;
+    let player_chunk = config.chunk_coord(player_transform.translation()); // chunk coords
+    let (cx, cz) = (player_chunk.x, player_chunk.z);
+    let render_distance = config.render_distance;
 
     let mut wanted_chunk = Vec::new();
-    for dx in -RENDER_DISTANCE..=RENDER_DISTANCE { // check which chunks should be loaded
-        for dz in -RENDER_DISTANCE..=RENDER_DISTANCE {
+    for dx in -render_distance..=render_distance { // check which chunks should be loaded
+        for dz in -render_distance..=render_distance {
             wanted_chunk.push(ChunkCoord { x: cx + dx, z: cz + dz });
         }
     }
+    // nearest chunks first, so the task pool works on them first
+    wanted_chunk.sort_by_key(|c| (c.x - cx).pow(2) + (c.z - cz).pow(2));
 
-    // load chunks
+    // start generating missing chunks (or chunks whose lod ring changed) in the background
+    let pool = AsyncComputeTaskPool::get();
     for coord in wanted_chunk.iter() {
-        if !loaded.chunks.contains_key(coord) {
-            let mesh = calc_to_generate_chunk(*coord, &noise);
-
-            let ent = commands.spawn((
-                Mesh3d(meshes.add(mesh)), 
-                MeshMaterial3d(materials.add(Color::srgb(1.0, 1.0, 1.0))),
-                Transform::from_xyz(
-                    coord.x as f32 * CHUNK_SIZE as f32 * VERTEX_SPACING,
-                    0.0,
-                    coord.z as f32 * CHUNK_SIZE as f32 * VERTEX_SPACING,
-                ),
-            )).id();
-
-            let chunk_x = coord.x as f32 * CHUNK_SIZE as f32 * VERTEX_SPACING;
-            let chunk_z = coord.z as f32 * CHUNK_SIZE as f32 * VERTEX_SPACING;
-            // load trees and other stuff
-            for z in 0..CHUNK_SIZE {
-                for x in 0..CHUNK_SIZE {
-
-                    let wx = chunk_x + x as f32 * VERTEX_SPACING;
-                    let wz = chunk_z + z as f32 * VERTEX_SPACING;
-
-                    let local_x = wx - chunk_x;
-                    let local_z = wz - chunk_z;
-
-                    if should_tree_spawn(wx as f64, wz as f64, &noise) {
-                        let h = get_height(wx as f64, wz as f64, &noise);
-
-                        spawn_tree(
-                            &mut commands,
-                            &tree_model,
-                            ent,
-                            local_x,
-                            local_z,
-                            h,
-                        );
-                    };
-
-                    if should_candy_spawn(wx as f64, wz as f64, &noise) {
-                        let h = get_height(wx as f64, wz as f64, &noise);
-
-                        spawn_candy_cane(
-                            &mut commands,
-                            &candy_cane,
-                            ent,
-                            local_x,
-                            local_z,
-                            h,
-                        );
-                    }
+        let lod = lod_for_distance((coord.x - cx).abs().max((coord.z - cz).abs()));
+
+        let up_to_date = loaded.chunks.get(coord).is_some_and(|c| c.lod == lod);
+        let queued = pending.tasks.get(coord).is_some_and(|p| p.lod == lod);
+        if !up_to_date && !queued {
+            let coord = *coord;
+            let noise = noise.clone();
+            let config = config.clone();
+            #[cfg(feature = "props")]
+            let prop_models = prop_models.clone();
+            let edits = edits.around(coord);
+            let task = pool.spawn(async move {
+                #[cfg_attr(not(feature = "props"), allow(unused_mut))]
+                let mut data = build_chunk(coord, lod, &noise, &config, &edits);
+                #[cfg(feature = "props")]
+                {
+                    data.prop_meshes = merge_props(&data.props, &prop_models);
                 }
-            }
-            loaded.chunks.insert(*coord, ent);
+                data
+            });
+            pending.tasks.insert(coord, PendingChunk { lod, task }); // replaces a task with an outdated lod
         }
     }
 
+    // drop tasks for chunks that are out of range again (dropping a task cancels it)
+    pending.tasks.retain(|coord, _| {
+        (coord.x - cx).abs() <= render_distance && (coord.z - cz).abs() <= render_distance
+    });
+
     //unload chunks
-        loaded.chunks.retain(|coord, ent| {
+        loaded.chunks.retain(|coord, chunk| {
         let dx = coord.x - cx;
         let dz = coord.z - cz;
 
-        if dx.abs() > RENDER_DISTANCE || dz.abs() > RENDER_DISTANCE {
-            commands.entity(*ent).despawn();
+        if dx.abs() > render_distance || dz.abs() > render_distance {
+            commands.entity(chunk.entity).despawn();
+            colliders.cells.remove(coord);
             false
         } else {
             true
@@ -129,81 +171,325 @@ pub fn chunk_system(
     });
 }
 
-#[derive(Resource)]
-pub struct TreeModel {
-    pub handle: Handle<Scene>,
+// the terrain changed (hot reload), throw away all chunks so they get generated again.
+// tuning the snow or the wind doesn't send TerrainChanged, that keeps the chunks and the footprints
+pub fn regenerate_chunks(
+    mut commands: Commands,
+    mut terrain_changed: EventReader<TerrainChanged>,
+    mut loaded: ResMut<LoadedChunks>,
+    mut pending: ResMut<PendingChunks>,
+    mut colliders: ResMut<ColliderGrid>,
+    mut edits: ResMut<TerrainEdits>,
+    mut remeshes: ResMut<PendingRemeshes>,
+) {
+    let Some(grid) = terrain_changed.read().map(|change| change.grid).reduce(|a, b| a || b) else { return };
+
+    for (_, chunk) in loaded.chunks.drain() {
+        commands.entity(chunk.entity).despawn();
+    }
+    pending.tasks.clear();
+    colliders.cells.clear();
+    remeshes.tasks.clear();
+    // the edits live on the vertex grid, a different chunk size or spacing wouldn't line up anymore
+    if grid {
+        *edits = TerrainEdits::default();
+    } else {
+        edits.dirty.clear(); // the new chunks get built with the edits anyway
+    }
 }
 
-fn load_tree_model(
+/// Everything a chunk needs, computed off the main thread.
+pub struct ChunkData {
+    pub mesh: Mesh,
+    pub ice: Option<Mesh>,
+    pub props: Vec<PropPlacement>,
+    pub prop_meshes: Vec<(Handle<StandardMaterial>, Mesh)>, // merged, one per prop material, empty until the models are merged in
+    pub colliders: Vec<PropCollider>,
+}
+
+/// One prop in a chunk, the transform is relative to the chunk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropPlacement {
+    pub kind: PropKind,
+    pub transform: Transform,
+}
+
+pub fn build_chunk(
+    coord: ChunkCoord,
+    lod: u32,
+    noise: &NoiseGenerators,
+    config: &WorldGenConfig,
+    edits: &TerrainEdits,
+) -> ChunkData {
+    let mesh = calc_to_generate_chunk(coord, lod, noise, config, edits);
+    let ice = calc_ice_mesh(coord, lod, noise, config);
+    // without the props feature there's nothing to see, so nothing to bump into either
+    let props = if cfg!(feature = "props") { prop_placements(coord, noise, config) } else { Vec::new() };
+
+    let origin = Vec3::new(coord.x as f32, 0.0, coord.z as f32) * config.chunk_world_size();
+    let colliders = props.iter().map(|p| prop_collider(p, origin)).collect();
+
+    ChunkData { mesh, ice, props, prop_meshes: Vec::new(), colliders }
+}
+
+// where every prop of a chunk goes. Only depends on the seed and world position,
+// so reloading a chunk (or restarting with the same seed) gives the exact same layout
+pub fn prop_placements(coord: ChunkCoord, noise: &NoiseGenerators, config: &WorldGenConfig) -> Vec<PropPlacement> {
+    let chunk_x = coord.x as f32 * config.chunk_world_size();
+    let chunk_z = coord.z as f32 * config.chunk_world_size();
+
+    let mut props = Vec::new();
+    // load trees and other stuff
+    for z in 0..config.chunk_size {
+        for x in 0..config.chunk_size {
+            // global grid position, this is what gets hashed
+            let gx = coord.x as i64 * config.chunk_size as i64 + x as i64;
+            let gz = coord.z as i64 * config.chunk_size as i64 + z as i64;
+
+            let wx = chunk_x + x as f32 * config.vertex_spacing;
+            let wz = chunk_z + z as f32 * config.vertex_spacing;
+
+            let kinds = [
+                (PropKind::Tree, should_tree_spawn(wx as f64, wz as f64, noise, config)),
+                (PropKind::CandyCane, should_candy_spawn(wx as f64, wz as f64, noise, config)),
+                (PropKind::Shrub, should_shrub_spawn(wx as f64, wz as f64, noise, config)),
+            ];
+
+            for (kind, spawn) in kinds {
+                if !spawn {
+                    continue;
+                }
+                let rand = |salt: u64| noise.hash01(gx, gz, salt + kind as u64 * 16);
+
+                // move it a bit off the grid so it doesn't look so lined up
+                let jitter = config.vertex_spacing * 0.35;
+                let px = wx + (rand(0) * 2.0 - 1.0) * jitter;
+                let pz = wz + (rand(1) * 2.0 - 1.0) * jitter;
+                let h = get_height(px as f64, pz as f64, noise, config);
+
+                let yaw = rand(2) * std::f32::consts::TAU;
+                let (rotation, scale) = match kind {
+                    PropKind::Tree => (
+                        Quat::from_rotation_y(yaw),
+                        Vec3::splat(1.25 * (0.8 + rand(3) * 0.45)),
+                    ),
+                    PropKind::CandyCane => {
+                        // slight tilt
+                        let tilt_x = (rand(3) * 20.0 - 10.0).to_radians();
+                        let tilt_z = (rand(4) * 20.0 - 10.0).to_radians();
+                        (
+                            Quat::from_rotation_y(yaw) * Quat::from_rotation_x(tilt_x) * Quat::from_rotation_z(tilt_z),
+                            Vec3::splat(5.0),
+                        )
+                    }
+                    PropKind::Shrub => {
+                        let size = 0.8 + rand(3) * 0.5;
+                        (Quat::from_rotation_y(yaw), Vec3::new(size, 0.55 * size, size))
+                    }
+                };
+
+                props.push(PropPlacement {
+                    kind,
+                    transform: Transform {
+                        translation: Vec3::new(px - chunk_x, h, pz - chunk_z),
+                        rotation,
+                        scale,
+                    },
+                });
+            }
+        }
+    }
+
+    props
+}
+
+// spawns finished chunks, nearest first and only a few per frame
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_finished_chunks(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    mut loaded: ResMut<LoadedChunks>,
+    mut pending: ResMut<PendingChunks>,
+    player_query: Query<&GlobalTransform, With<FlyCamera>>,
+    terrain_materials: Res<TerrainMaterials>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    config: Res<WorldGenConfig>,
+    mut colliders: ResMut<ColliderGrid>,
 ) {
-    let tree: Handle<Scene> = asset_server.load("snowy_pine_tree.glb#Scene0");
+    let Ok(player_transform) = player_query.single() else { return };
+    let player_chunk = config.chunk_coord(player_transform.translation());
+    let (cx, cz) = (player_chunk.x, player_chunk.z);
+
+    let mut finished: Vec<ChunkCoord> = pending.tasks.iter()
+        .filter(|(_, pending)| pending.task.is_finished())
+        .map(|(coord, _)| *coord)
+        .collect();
+    finished.sort_by_key(|c| (c.x - cx).pow(2) + (c.z - cz).pow(2));
+
+    for coord in finished.into_iter().take(MAX_CHUNKS_PER_FRAME) {
+        let Some(PendingChunk { lod, task }) = pending.tasks.remove(&coord) else { continue };
+        // already finished, so this doesn't block
+        let data = block_on(task);
+
+        // lod rebuild: the new mesh replaces the old chunk entity
+        if let Some(old) = loaded.chunks.remove(&coord) {
+            commands.entity(old.entity).despawn();
+        }
+
+        let mesh = meshes.add(data.mesh);
+        let ent = commands.spawn((
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(terrain_materials.snow.clone()),
+            Transform::from_xyz(
+                coord.x as f32 * config.chunk_world_size(),
+                0.0,
+                coord.z as f32 * config.chunk_world_size(),
+            ),
+        )).id();
+
+        commands.entity(ent).with_children(|parent| {
+            if let Some(ice) = data.ice {
+                parent.spawn((
+                    Mesh3d(meshes.add(ice)),
+                    MeshMaterial3d(terrain_materials.ice.clone()),
+                    Transform::default(),
+                ));
+            }
 
-    commands.insert_resource(TreeModel { handle: tree });
+            // all props of a chunk, one draw per material
+            for (material, mesh) in data.prop_meshes {
+                parent.spawn((
+                    Mesh3d(meshes.add(mesh)),
+                    MeshMaterial3d(material),
+                    Transform::default(),
+                ));
+            }
+        });
+
+        loaded.chunks.insert(coord, LoadedChunk { entity: ent, lod, mesh });
+        colliders.cells.insert(coord, data.colliders);
+    }
 }
 
-fn spawn_tree(
-    commands: &mut Commands,
-    tree_model: &TreeModel,
-    parent: Entity,
-    x: f32,
-    z: f32,
-    height: f32,
+// starts rebuilding the terrain mesh of chunks that got footprints, tracks or craters
+fn remesh_deformed_chunks(
+    loaded: Res<LoadedChunks>,
+    pending: Res<PendingChunks>,
+    mut remeshes: ResMut<PendingRemeshes>,
+    mut edits: ResMut<TerrainEdits>,
+    noise: Res<NoiseGenerators>,
+    config: Res<WorldGenConfig>,
 ) {
-    commands.entity(parent).with_children(|parent| {
-        parent.spawn((
-            SceneRoot(tree_model.handle.clone()),
-            Transform::from_xyz(x, height, z).with_scale(Vec3::splat(1.25)),
-        ));
-    });
+    if edits.dirty.is_empty() {
+        return;
+    }
+
+    let pool = AsyncComputeTaskPool::get();
+    let dirty: Vec<ChunkCoord> = edits.dirty.iter().copied().collect();
+    for coord in dirty {
+        // a full build is already running, maybe with old edits, try again once it's loaded
+        if pending.tasks.contains_key(&coord) || remeshes.tasks.contains_key(&coord) {
+            continue;
+        }
+        edits.dirty.remove(&coord);
+        // not loaded, it picks up the edits when it gets generated
+        let Some(chunk) = loaded.chunks.get(&coord) else { continue };
+
+        let lod = chunk.lod;
+        let noise = noise.clone();
+        let config = config.clone();
+        let around = edits.around(coord);
+        let task = pool.spawn(async move { calc_to_generate_chunk(coord, lod, &noise, &config, &around) });
+        remeshes.tasks.insert(coord, PendingRemesh { lod, task });
+    }
 }
 
+// swaps the finished meshes into the chunks, the entity stays the same
+fn apply_finished_remeshes(
+    loaded: Res<LoadedChunks>,
+    mut remeshes: ResMut<PendingRemeshes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let finished: Vec<ChunkCoord> = remeshes.tasks.iter()
+        .filter(|(_, remesh)| remesh.task.is_finished())
+        .map(|(coord, _)| *coord)
+        .collect();
+
+    for coord in finished {
+        let Some(PendingRemesh { lod, task }) = remeshes.tasks.remove(&coord) else { continue };
+        let mesh = block_on(task);
+
+        // chunk got unloaded or changed its lod in the meantime, the new build already has the edits
+        if let Some(chunk) = loaded.chunks.get(&coord).filter(|c| c.lod == lod) {
+            meshes.insert(&chunk.mesh, mesh);
+        }
+    }
+}
+
+// shared materials for all chunks
 #[derive(Resource)]
-pub struct CandyCane {
-    pub handle: Handle<Scene>,
+pub struct TerrainMaterials {
+    pub snow: Handle<StandardMaterial>,
+    pub ice: Handle<StandardMaterial>,
 }
 
-fn load_candy_cane(
+fn setup_terrain_materials(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    let candy_cane: Handle<Scene> = asset_server.load("candy_cane.glb#Scene0");
+    commands.insert_resource(TerrainMaterials {
+        snow: materials.add(Color::srgb(1.0, 1.0, 1.0)),
+        // glossy ice on frozen lakes
+        ice: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.70, 0.85, 0.97),
+            perceptual_roughness: 0.08,
+            reflectance: 0.7,
+            ..default()
+        }),
+    });
+}
 
-    commands.insert_resource(CandyCane { handle: candy_cane });
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("loaded candycane")
-}
+    fn coords() -> impl Iterator<Item = ChunkCoord> {
+        (-3..3).flat_map(|x| (-3..3).map(move |z| ChunkCoord { x, z }))
+    }
 
-fn spawn_candy_cane(
-    commands: &mut Commands,
-    candy_cane: &CandyCane,
-    parent: Entity,
-    x: f32,
-    z: f32,
-    height: f32,
-) {
-    let mut rng = rand::thread_rng();
-
-    // for random rotation
-    let yaw = rng.gen_range(0.0_f32..360.0).to_radians();
-
-    // slight tilt
-    let tilt_x = rng.gen_range(-10.0_f32..10.0).to_radians();
-    let tilt_z = rng.gen_range(-10.0_f32..10.0).to_radians();
-    // random rotation
-    let rotation = Quat::from_rotation_y(yaw)
-        * Quat::from_rotation_x(tilt_x)
-        * Quat::from_rotation_z(tilt_z);
-
-    commands.entity(parent).with_children(|parent| {
-        parent.spawn((
-            SceneRoot(candy_cane.handle.clone()),
-            Transform {
-                translation: Vec3::new(x, height, z),
-                rotation,
-                scale: Vec3::splat(5.0),
-            },
-        ));
-    });
+    #[test]
+    fn same_seed_gives_same_props() {
+        let config = WorldGenConfig::default();
+        let first = NoiseGenerators::new(67);
+        let restarted = NoiseGenerators::new(67);
+
+        for coord in coords() {
+            let placements = prop_placements(coord, &first, &config);
+            // reloading the chunk
+            assert_eq!(placements, prop_placements(coord, &first, &config));
+            // restarting the game with the same seed
+            assert_eq!(placements, prop_placements(coord, &restarted, &config));
+        }
+    }
+
+    #[test]
+    fn lod_rebuild_keeps_props() {
+        let config = WorldGenConfig::default();
+        let noise = NoiseGenerators::new(67);
+        let coord = ChunkCoord { x: 1, z: -2 };
+        let edits = TerrainEdits::default();
+
+        assert_eq!(build_chunk(coord, 0, &noise, &config, &edits).props, build_chunk(coord, 3, &noise, &config, &edits).props);
+    }
+
+    #[test]
+    fn different_seed_gives_different_props() {
+        let config = WorldGenConfig::default();
+        let a = NoiseGenerators::new(67);
+        let b = NoiseGenerators::new(68);
+
+        let all_a: Vec<_> = coords().flat_map(|c| prop_placements(c, &a, &config)).collect();
+        let all_b: Vec<_> = coords().flat_map(|c| prop_placements(c, &b, &config)).collect();
+        assert!(!all_a.is_empty());
+        assert_ne!(all_a, all_b);
+    }
 }
@@ -0,0 +1,110 @@
+// runs the world without a window, like other apps and tools embedding WinterPlugins would
+
+use bevy::input::ButtonState;
+use bevy::input::keyboard::{Key, KeyboardInput, NativeKey};
+use bevy::prelude::*;
+use bevy_winter::WinterPlugins;
+use bevy_winter::headless::HeadlessPlugin;
+use bevy_winter::noise::NoiseGenerators;
+use bevy_winter::config::WorldGenConfig;
+use bevy_winter::deform::TerrainEdits;
+use bevy_winter::player::{FlyCamera, PlayerMode};
+use bevy_winter::snowball::{Snowball, SnowballScore, SnowballTarget};
+use bevy_winter::world_gen::LoadedChunks;
+
+fn headless_app(seed: u32) -> App {
+    let save_dir = std::env::temp_dir().join(format!("bevy_winter_test_{seed}_{}", std::process::id()));
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        HeadlessPlugin,
+        WinterPlugins::default()
+            .seed(seed)
+            .snow(false)
+            .hud(false)
+            .wireframe(false)
+            .render_distance(Some(2))
+            .save_dir(Some(save_dir)),
+    ));
+    app
+}
+
+// updates until the condition holds, chunks are built on the task pool so this takes a few frames
+fn update_until(app: &mut App, done: impl Fn(&mut App) -> bool) -> bool {
+    for _ in 0..2000 {
+        app.update();
+        if done(app) {
+            return true;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+    false
+}
+
+#[test]
+fn chunks_load_around_the_player() {
+    let mut app = headless_app(7);
+    // render distance 2 = 5x5 chunks
+    let loaded = update_until(&mut app, |app| app.world().resource::<LoadedChunks>().chunks.len() == 25);
+    assert!(loaded, "chunks never finished loading");
+
+    assert_eq!(app.world().resource::<NoiseGenerators>().seed, 7);
+    let mut cameras = app.world_mut().query::<&FlyCamera>();
+    assert_eq!(cameras.iter(app.world()).count(), 1);
+}
+
+fn press(app: &mut App, key: KeyCode) {
+    for state in [ButtonState::Pressed, ButtonState::Released] {
+        app.world_mut().send_event(KeyboardInput {
+            key_code: key,
+            logical_key: Key::Unidentified(NativeKey::Unidentified),
+            state,
+            text: None,
+            repeat: false,
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+    }
+}
+
+fn mode(app: &App) -> PlayerMode {
+    *app.world().resource::<State<PlayerMode>>().get()
+}
+
+#[test]
+fn flying_toggles_and_lands_again() {
+    let mut app = headless_app(8);
+    assert!(update_until(&mut app, |app| !app.world().resource::<LoadedChunks>().chunks.is_empty()));
+    assert!(update_until(&mut app, |app| matches!(mode(app), PlayerMode::Walking | PlayerMode::Skating)));
+
+    press(&mut app, KeyCode::KeyF);
+    assert_eq!(mode(&app), PlayerMode::Flying);
+
+    // stop flying = falling until the ground is there
+    press(&mut app, KeyCode::KeyF);
+    assert!(update_until(&mut app, |app| matches!(mode(app), PlayerMode::Walking | PlayerMode::Skating)));
+}
+
+#[test]
+fn snowballs_hit_actors_and_the_ground() {
+    let mut app = headless_app(9);
+    assert!(update_until(&mut app, |app| matches!(mode(app), PlayerMode::Walking | PlayerMode::Skating)));
+
+    let mut players = app.world_mut().query_filtered::<&Transform, With<FlyCamera>>();
+    let eyes = players.single(app.world()).unwrap().translation;
+
+    // a target right next to the player, and a ball flying into it
+    app.world_mut().spawn((SnowballTarget { radius: 0.5 }, Transform::from_translation(eyes + Vec3::X * 3.0)));
+    app.world_mut().spawn((Snowball { velocity: Vec3::X * 20.0 }, Transform::from_translation(eyes + Vec3::X)));
+    assert!(update_until(&mut app, |app| app.world().resource::<SnowballScore>().points == 5));
+
+    // straight down leaves a crater, deeper than the footprint the player stands in
+    let offset = |app: &App| {
+        let config = app.world().resource::<WorldGenConfig>();
+        app.world().resource::<TerrainEdits>().offset_at(eyes.x as f64, eyes.z as f64, config)
+    };
+    let footprint = offset(&app);
+    app.world_mut().spawn((Snowball { velocity: Vec3::NEG_Y * 20.0 }, Transform::from_translation(eyes)));
+    assert!(update_until(&mut app, |app| offset(app) < footprint - 0.03));
+    assert_eq!(app.world().resource::<SnowballScore>().hits, 1);
+}